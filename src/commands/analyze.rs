@@ -0,0 +1,40 @@
+use clap::{Args, ValueEnum, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    #[clap(
+        help = "Set the input RPU or HEVC file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        help = "Set the output report file location. Print to console if not set",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size"
+    )]
+    pub size: Vec<usize>,
+
+    #[clap(
+        short = 'f',
+        long,
+        default_value = "text",
+        help = "Set the format of report"
+    )]
+    pub format: ReportFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
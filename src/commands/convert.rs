@@ -1,10 +1,12 @@
-use clap::{Args, ValueHint};
+use clap::{Args, ValueEnum, ValueHint};
 use std::path::PathBuf;
 
+use crate::commands::CutDetectionArgs;
+
 #[derive(Args, Debug)]
 pub struct ConvertArgs {
     #[clap(
-        help = "Set the input RPU file to use",
+        help = "Set the input RPU or HEVC file to use",
         value_hint = ValueHint::FilePath
     )]
     pub input: Option<PathBuf>,
@@ -40,6 +42,14 @@ pub struct ConvertArgs {
     )]
     pub use_level6: bool,
 
+    #[clap(
+        short = 'e',
+        long,
+        conflicts_with = "use_level6",
+        help = "Use MaxCLL and MaxFALL estimated from Level 1 of all frames"
+    )]
+    pub estimate_level6: bool,
+
     #[clap(short = 'd', long, help = "Drop per-frame metadata in shots")]
     pub drop_per_frame: bool,
 
@@ -73,4 +83,216 @@ pub struct ConvertArgs {
         help = "Set the number of frames to be added to the index"
     )]
     pub offset: usize,
+
+    #[clap(
+        short = 'm',
+        long,
+        help = "Set the mastering display by predefined or user-defined ID"
+    )]
+    pub mastering_display: Option<usize>,
+
+    #[clap(
+        long,
+        conflicts_with = "mastering_display",
+        help = "Set the peak brightness of custom mastering display in nits"
+    )]
+    pub mastering_peak: Option<usize>,
+
+    #[clap(
+        long,
+        requires = "mastering_peak",
+        help = "Set the minimum brightness of custom mastering display in nits. Use the predefined one if possible, or 0.005 if not set"
+    )]
+    pub mastering_min: Option<f32>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        requires = "mastering_peak",
+        help = "Set the primaries of custom mastering display. Format: Rx,Ry,Gx,Gy,Bx,By"
+    )]
+    pub mastering_primaries: Option<Vec<f32>>,
+
+    #[clap(
+        long,
+        default_value = "0.3127,0.329",
+        value_delimiter = ',',
+        requires = "mastering_peak",
+        help = "Set the white point of custom mastering display. Format: x,y"
+    )]
+    pub mastering_white_point: Vec<f32>,
+
+    #[clap(
+        long,
+        default_value = "2",
+        help = "Set the primaries index of color encoding. Common values: 0 (P3 D65), 1 (BT.709), 2 (BT.2020)"
+    )]
+    pub encoding_primaries: usize,
+
+    #[clap(long, default_value = "pq", help = "Set the EOTF of color encoding")]
+    pub eotf: Eotf,
+
+    #[clap(
+        long,
+        default_value = "rgb",
+        help = "Set the color space of color encoding"
+    )]
+    pub color_space: ColorSpaceType,
+
+    #[clap(
+        long,
+        default_value = "computer",
+        help = "Set the signal range of color encoding"
+    )]
+    pub signal_range: SignalRangeType,
+
+    #[clap(long, help = "Set the bit depth of color encoding")]
+    pub bit_depth: Option<usize>,
+
+    #[clap(long, help = "Set the chroma format of color encoding")]
+    pub chroma_format: Option<ChromaFormatType>,
+
+    #[clap(
+        long,
+        default_value = "random",
+        help = "Set the source of unique IDs in XML"
+    )]
+    pub unique_id: UniqueIdType,
+
+    #[clap(
+        long,
+        help = "Set the time of revision in seconds since Unix epoch. SOURCE_DATE_EPOCH is used if not set"
+    )]
+    pub timestamp: Option<i64>,
+
+    #[clap(
+        long,
+        help = "Add the RPU or HEVC file of a reel following INPUT. Can be used multiple times",
+        value_hint = ValueHint::FilePath
+    )]
+    pub reel: Vec<PathBuf>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        conflicts_with = "offset",
+        help = "Set the first frame in timeline of each reel, starting from INPUT. Reels follow the previous one if not set"
+    )]
+    pub reel_offset: Vec<usize>,
+
+    #[clap(
+        long,
+        default_value = "conformed",
+        help = "Set the layout of multiple reels in XML"
+    )]
+    pub reel_layout: ReelLayoutType,
+
+    #[clap(flatten)]
+    pub cut_detection: CutDetectionArgs,
+
+    #[clap(
+        long,
+        help = "Set a CMX3600 EDL to use its record in and out points as shot boundaries",
+        value_hint = ValueHint::FilePath
+    )]
+    pub edl: Option<PathBuf>,
+
+    #[clap(
+        long,
+        default_value = "replace",
+        requires = "edl",
+        help = "Set how cuts in EDL are used with cuts in RPU"
+    )]
+    pub edl_mode: EdlModeType,
+
+    #[clap(
+        long,
+        requires = "edl",
+        help = "Set the record timecode in EDL of the first frame in timeline. Record in of the first event is used if not set"
+    )]
+    pub edl_start: Option<String>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        conflicts_with = "drop_targets",
+        help = "Keep only the target displays with these IDs. Format: ID,ID,..."
+    )]
+    pub keep_targets: Option<Vec<usize>>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "Drop the target displays with these IDs, and their Level 2 and Level 8 trims. Format: ID,ID,..."
+    )]
+    pub drop_targets: Vec<usize>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "Add predefined or user-defined target displays with identity trims, if not found. Format: ID,ID,..."
+    )]
+    pub add_targets: Vec<usize>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum EdlModeType {
+    Replace,
+    Merge,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReelLayoutType {
+    Conformed,
+    Outputs,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum UniqueIdType {
+    Random,
+    Content,
+    Timecode,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Eotf {
+    Pq,
+    Linear,
+    #[value(name = "bt1886")]
+    GammaBt1886,
+    #[value(name = "dci")]
+    GammaDci,
+    #[value(name = "gamma22")]
+    Gamma22,
+    #[value(name = "gamma24")]
+    Gamma24,
+    Hlg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ColorSpaceType {
+    Rgb,
+    Xyz,
+    #[value(name = "ycbcr-bt709")]
+    YCbCrBt709,
+    #[value(name = "ycbcr-bt2020")]
+    YCbCrBt2020,
+    #[value(name = "ycbcr-native")]
+    YCbCrNative,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SignalRangeType {
+    Computer,
+    Video,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ChromaFormatType {
+    #[value(name = "444")]
+    Yuv444,
+    #[value(name = "422")]
+    Yuv422,
+    #[value(name = "420")]
+    Yuv420,
 }
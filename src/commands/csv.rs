@@ -0,0 +1,59 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ExportCsvArgs {
+    #[clap(
+        help = "Set the input RPU or XML file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        help = "Set the output CSV file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(long, help = "Add a row for each frame with per-frame metadata")]
+    pub per_frame: bool,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size of input RPU"
+    )]
+    pub size: Vec<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportCsvArgs {
+    #[clap(
+        help = "Set the input RPU or XML file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        help = "Set the edited CSV file to apply",
+        value_hint = ValueHint::FilePath
+    )]
+    pub csv: PathBuf,
+
+    #[clap(
+        help = "Set the output RPU or XML file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size of input RPU"
+    )]
+    pub size: Vec<usize>,
+}
@@ -0,0 +1,50 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+use crate::commands::analyze::ReportFormat;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[clap(
+        help = "Set the original RPU or XML file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        help = "Set the RPU or XML file to compare with",
+        value_hint = ValueHint::FilePath
+    )]
+    pub other: PathBuf,
+
+    #[clap(
+        help = "Set the output report file location. Print to console if not set",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        short = 't',
+        long,
+        default_value = "0.001",
+        help = "Set the maximum difference of values to be ignored, values are in range -1 to 1 or 0 to 1"
+    )]
+    pub tolerance: f32,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size of input RPU"
+    )]
+    pub size: Vec<usize>,
+
+    #[clap(
+        short = 'f',
+        long,
+        default_value = "text",
+        help = "Set the format of report"
+    )]
+    pub format: ReportFormat,
+}
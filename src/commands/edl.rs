@@ -1,10 +1,12 @@
-use clap::{Args, ValueHint};
+use clap::{Args, ValueEnum, ValueHint};
 use std::path::PathBuf;
 
+use crate::commands::CutDetectionArgs;
+
 #[derive(Args, Debug)]
 pub struct EdlArgs {
     #[clap(
-    help = "Set the input RPU file to use",
+    help = "Set the input RPU or HEVC file to use",
     value_hint = ValueHint::FilePath
     )]
     pub input: Option<PathBuf>,
@@ -12,8 +14,9 @@ pub struct EdlArgs {
     #[clap(
     help = "Set the output EDL file location. See --help for more info",
     long_help = "Set the output EDL file location.\n \
-                 If there are too many cuts to be saved in a single file,\n \
-                 multiple files will be saved with a suffix added to the file name.",
+                 If there are too many cuts to be saved in a single CMX3600 file,\n \
+                 multiple files will be saved with a suffix added to the file name.\n \
+                 OpenTimelineIO, FCPXML and scene cut lists are always saved in a single file.",
     value_hint = ValueHint::FilePath
     )]
     pub output: Option<PathBuf>,
@@ -32,6 +35,18 @@ pub struct EdlArgs {
     )]
     pub force: bool,
 
+    #[clap(
+        long,
+        help = "Detect dissolves from ramps of Level 1 across cuts in per-frame RPU"
+    )]
+    pub detect_dissolves: bool,
+
+    #[clap(
+        long,
+        help = "Set the output format. Determined by the extension of OUTPUT if not set"
+    )]
+    pub format: Option<EdlFormatType>,
+
     #[clap(
         short = 'r',
         long,
@@ -49,6 +64,12 @@ pub struct EdlArgs {
     )]
     pub start_timecode: String,
 
+    #[clap(
+        long,
+        help = "Use drop-frame timecode, only for multiples of 30000/1001"
+    )]
+    pub drop_frame: bool,
+
     #[clap(
         short = 't',
         long,
@@ -63,4 +84,18 @@ pub struct EdlArgs {
         help = "Set the number of frames to be parsed explicitly"
     )]
     pub count: Option<usize>,
+
+    #[clap(flatten)]
+    pub cut_detection: CutDetectionArgs,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum EdlFormatType {
+    Cmx3600,
+    Otio,
+    Fcpxml,
+    Qpfile,
+    Ffmpeg,
+    Chapters,
+    Frames,
 }
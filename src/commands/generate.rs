@@ -0,0 +1,44 @@
+use clap::{Args, ValueEnum, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    #[clap(
+        help = "Set the input XML file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        help = "Set the output RPU file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size"
+    )]
+    pub size: Vec<usize>,
+
+    #[clap(
+        short = 'p',
+        long,
+        default_value = "8.1",
+        help = "Set the Dolby Vision profile of generated RPU"
+    )]
+    pub profile: Profile,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Profile {
+    #[value(name = "5")]
+    Profile5,
+    #[value(name = "8.1")]
+    Profile81,
+    #[value(name = "8.4")]
+    Profile84,
+}
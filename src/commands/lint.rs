@@ -0,0 +1,51 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+use crate::commands::analyze::ReportFormat;
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    #[clap(
+        help = "Set the input RPU or XML file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        help = "Set the output report file location. Print to console if not set",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        short = 'm',
+        long,
+        help = "Set the minimum duration of shots in frames. Not checked if not set"
+    )]
+    pub min_shot_duration: Option<usize>,
+
+    #[clap(
+        short = 'r',
+        long,
+        value_delimiter = ',',
+        help = "Set the target displays which require trims in all shots, like 1 for 100-nit BT.709. Format: ID,ID,..."
+    )]
+    pub require_targets: Vec<usize>,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size of input RPU"
+    )]
+    pub size: Vec<usize>,
+
+    #[clap(
+        short = 'f',
+        long,
+        default_value = "text",
+        help = "Set the format of report"
+    )]
+    pub format: ReportFormat,
+}
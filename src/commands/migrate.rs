@@ -0,0 +1,30 @@
+use clap::{Args, ValueEnum, ValueHint};
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    #[clap(
+        help = "Set the input XML file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: Option<PathBuf>,
+
+    #[clap(
+        help = "Set the output XML file location",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(short = 'x', long, help = "Set the version of output XML")]
+    pub xml_version: XmlVersion,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum XmlVersion {
+    #[value(name = "2.0.5")]
+    V205,
+    #[value(name = "4.0.2")]
+    V402,
+    #[value(name = "5.1.0")]
+    V510,
+}
@@ -1,10 +1,23 @@
+pub mod analyze;
 pub mod convert;
+pub mod csv;
+pub mod diff;
 pub mod edl;
+pub mod generate;
+pub mod lint;
+pub mod migrate;
+pub mod verify;
 
-// use crate::commands::analyze::AnalyzeArgs;
+use crate::commands::analyze::AnalyzeArgs;
 use crate::commands::convert::ConvertArgs;
+use crate::commands::csv::{ExportCsvArgs, ImportCsvArgs};
+use crate::commands::diff::DiffArgs;
 use crate::commands::edl::EdlArgs;
-use clap::Parser;
+use crate::commands::generate::GenerateArgs;
+use crate::commands::lint::LintArgs;
+use crate::commands::migrate::MigrateArgs;
+use crate::commands::verify::VerifyArgs;
+use clap::{Args, Parser};
 
 #[derive(Parser, Debug)]
 pub enum Command {
@@ -12,11 +25,84 @@ pub enum Command {
         about = "Convert a binary RPU to XML Metadata (DolbyLabsMDF)",
         arg_required_else_help(true)
     )]
-    Convert(ConvertArgs),
+    Convert(Box<ConvertArgs>),
 
     #[clap(
         about = "Convert a binary RPU to EDL (Edit Decision List)",
         arg_required_else_help(true)
     )]
     Edl(EdlArgs),
+
+    #[clap(
+        about = "Generate a binary RPU from XML Metadata (DolbyLabsMDF)",
+        arg_required_else_help(true)
+    )]
+    Generate(GenerateArgs),
+
+    #[clap(
+        about = "Convert XML Metadata (DolbyLabsMDF) to another version",
+        arg_required_else_help(true)
+    )]
+    Migrate(MigrateArgs),
+
+    #[clap(
+        about = "Analyze a binary RPU and report statistics of metadata",
+        arg_required_else_help(true)
+    )]
+    Analyze(AnalyzeArgs),
+
+    #[clap(
+        about = "Export metadata of shots in a binary RPU or XML to CSV",
+        arg_required_else_help(true)
+    )]
+    ExportCsv(ExportCsvArgs),
+
+    #[clap(
+        about = "Apply an edited CSV to a binary RPU or XML",
+        arg_required_else_help(true)
+    )]
+    ImportCsv(ImportCsvArgs),
+
+    #[clap(
+        about = "Compare two binary RPUs or XMLs and report the changes shot by shot",
+        arg_required_else_help(true)
+    )]
+    Diff(DiffArgs),
+
+    #[clap(
+        about = "Verify that XML Metadata reproduces the code values of a binary RPU",
+        arg_required_else_help(true)
+    )]
+    Verify(VerifyArgs),
+
+    #[clap(
+        about = "Check a binary RPU or XML against rules of delivery",
+        arg_required_else_help(true)
+    )]
+    Lint(LintArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CutDetectionArgs {
+    #[clap(
+        long,
+        help = "Detect cuts from Level 1, Level 2 and Level 8 of per-frame RPU"
+    )]
+    pub detect_cuts: bool,
+
+    #[clap(
+        long,
+        default_value = "0.03",
+        requires = "detect_cuts",
+        help = "Set the minimum jump of min, avg or max PQ in Level 1 for a cut, in range 0 to 1"
+    )]
+    pub cut_level1_threshold: f32,
+
+    #[clap(
+        long,
+        default_value = "0.05",
+        requires = "detect_cuts",
+        help = "Set the minimum change of trims in Level 2 or Level 8 for a cut, trims are in range -1 to 1"
+    )]
+    pub cut_trim_threshold: f32,
 }
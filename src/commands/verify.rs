@@ -0,0 +1,52 @@
+use clap::{Args, ValueHint};
+use std::path::PathBuf;
+
+use crate::commands::analyze::ReportFormat;
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    #[clap(
+        help = "Set the source RPU or HEVC file to use",
+        value_hint = ValueHint::FilePath
+    )]
+    pub input: PathBuf,
+
+    #[clap(
+        help = "Set the output report file location. Print to console if not set",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        short = 'x',
+        long,
+        help = "Set the XML file to verify. Converted from INPUT if not set",
+        value_hint = ValueHint::FilePath
+    )]
+    pub xml: Option<PathBuf>,
+
+    #[clap(
+        short = 't',
+        long,
+        default_value = "1",
+        help = "Set the maximum error of code values to be accepted"
+    )]
+    pub threshold: u16,
+
+    #[clap(
+        short = 's',
+        long,
+        default_value = "3840x2160",
+        value_delimiter = 'x',
+        help = "Set the canvas size"
+    )]
+    pub size: Vec<usize>,
+
+    #[clap(
+        short = 'f',
+        long,
+        default_value = "text",
+        help = "Set the format of report"
+    )]
+    pub format: ReportFormat,
+}
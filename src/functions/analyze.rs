@@ -0,0 +1,470 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::cmv40::{Shot, ShotPluginNode};
+use crate::commands::analyze::{AnalyzeArgs, ReportFormat};
+use crate::display::pq2l;
+use crate::functions::input::Input;
+use crate::functions::{RpuReader, ShotOptions, ShotReader};
+use crate::{Level5, Level6};
+
+#[derive(Debug)]
+pub struct Analyzer {
+    frame_count: usize,
+    shot_count: usize,
+    shortest_shot: Option<ShotSummary>,
+    longest_shot: Option<ShotSummary>,
+    per_frame_shot_count: usize,
+    per_frame_frame_count: usize,
+    // In nits
+    level1_min: f32,
+    level1_avg_sum: f64,
+    level1_max: f32,
+    level6: Level6,
+    target_ids: BTreeSet<usize>,
+    level5: Option<Level5>,
+    aspect_ratios: Vec<AspectRatioChange>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            frame_count: 0,
+            shot_count: 0,
+            shortest_shot: None,
+            longest_shot: None,
+            per_frame_shot_count: 0,
+            per_frame_frame_count: 0,
+            level1_min: f32::MAX,
+            level1_avg_sum: 0.0,
+            level1_max: 0.0,
+            level6: Level6::default(),
+            target_ids: BTreeSet::new(),
+            level5: None,
+            aspect_ratios: Vec::new(),
+        }
+    }
+}
+
+impl Analyzer {
+    pub fn analyze(args: AnalyzeArgs) -> Result<()> {
+        let input = match args.input {
+            Some(input) => input,
+            None => bail!("No input file provided."),
+        };
+
+        let options = ShotOptions {
+            canvas: Input::canvas(&args.size)?,
+            ..Default::default()
+        };
+
+        let mut analyzer = Analyzer::default();
+        let mut shot_reader = ShotReader::new(RpuReader::open(&input)?, options);
+
+        for shot in shot_reader.by_ref() {
+            analyzer.update(&shot?);
+        }
+
+        let Some(track) = shot_reader.take_track() else {
+            bail!("No valid RPU found.");
+        };
+
+        let report = analyzer.into_report(track.level6);
+
+        let report = match args.format {
+            ReportFormat::Text => report.to_string(),
+            ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        };
+
+        if let Some(output) = args.output {
+            let mut writer = BufWriter::new(File::create(output)?);
+            writeln!(writer, "{report}")?;
+            writer.flush()?;
+        } else {
+            println!("{report}");
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, shot: &Shot) {
+        let summary = ShotSummary {
+            start: shot.record.in_,
+            duration: shot.record.duration,
+        };
+
+        if self
+            .shortest_shot
+            .as_ref()
+            .map_or(true, |s| summary.duration < s.duration)
+        {
+            self.shortest_shot = Some(summary.clone());
+        }
+
+        if self
+            .longest_shot
+            .as_ref()
+            .map_or(true, |s| summary.duration > s.duration)
+        {
+            self.longest_shot = Some(summary);
+        }
+
+        self.shot_count += 1;
+
+        let frames = shot.frames.as_deref().unwrap_or_default();
+
+        if !frames.is_empty() {
+            self.per_frame_shot_count += 1;
+            self.per_frame_frame_count += frames.len();
+        }
+
+        let mut frames = frames.iter().peekable();
+
+        for offset in 0..shot.record.duration {
+            let plugin_node = match frames.next_if(|frame| frame.edit_offset == offset) {
+                Some(frame) => &frame.plugin_node,
+                None => &shot.plugin_node,
+            };
+
+            self.update_frame(shot.record.in_ + offset, plugin_node);
+        }
+    }
+
+    fn update_frame(&mut self, index: usize, plugin_node: &ShotPluginNode) {
+        let data = &plugin_node.dv_dynamic_data;
+
+        let (min, avg, max) = data
+            .level1
+            .image_character
+            .into_inner()
+            .into_iter()
+            .map(pq2l)
+            .collect_tuple()
+            .unwrap_or_default();
+
+        self.frame_count += 1;
+        self.level1_min = self.level1_min.min(min);
+        self.level1_avg_sum += avg as f64;
+        self.level1_max = self.level1_max.max(max);
+        self.level6.update_with_level1(&data.level1);
+
+        self.target_ids
+            .extend(data.level2.iter().flatten().map(|l2| l2.tid));
+        self.target_ids
+            .extend(data.level8.iter().flatten().map(|l8| l8.tid as usize));
+
+        if data.level5.is_some() && data.level5 != self.level5 {
+            self.level5.clone_from(&data.level5);
+
+            if let Some(level5) = &data.level5 {
+                let (canvas, image) = level5.get_ar();
+
+                self.aspect_ratios.push(AspectRatioChange {
+                    frame: index,
+                    canvas,
+                    image,
+                });
+            }
+        }
+    }
+
+    fn into_report(self, level6: Option<Level6>) -> Report {
+        let frame_count = self.frame_count.max(1);
+
+        Report {
+            frames: self.frame_count,
+            shots: ShotsReport {
+                count: self.shot_count,
+                shortest: self.shortest_shot.unwrap_or_default(),
+                longest: self.longest_shot.unwrap_or_default(),
+            },
+            per_frame_metadata: PerFrameReport {
+                shots: self.per_frame_shot_count,
+                frames: self.per_frame_frame_count,
+            },
+            level1: Level1Report {
+                min: self.level1_min.min(self.level1_max),
+                avg: (self.level1_avg_sum / frame_count as f64) as f32,
+                max: self.level1_max,
+            },
+            target_displays: self.target_ids.into_iter().collect(),
+            aspect_ratios: self.aspect_ratios,
+            level6: Level6Report {
+                is_close: level6
+                    .as_ref()
+                    .map_or(true, |l6| l6.is_close_to(&self.level6)),
+                rpu: level6.map(LightLevels::from),
+                computed: LightLevels::from(self.level6),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct ShotSummary {
+    start: usize,
+    duration: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct AspectRatioChange {
+    frame: usize,
+    canvas: f32,
+    image: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct LightLevels {
+    max_cll: usize,
+    max_fall: usize,
+}
+
+impl From<Level6> for LightLevels {
+    fn from(level6: Level6) -> Self {
+        Self {
+            max_cll: level6.max_cll,
+            max_fall: level6.max_fall,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    frames: usize,
+    shots: ShotsReport,
+    per_frame_metadata: PerFrameReport,
+    // In nits
+    level1: Level1Report,
+    // Target display IDs with trims in Level2 or Level8
+    target_displays: Vec<usize>,
+    aspect_ratios: Vec<AspectRatioChange>,
+    level6: Level6Report,
+}
+
+#[derive(Debug, Serialize)]
+struct ShotsReport {
+    count: usize,
+    shortest: ShotSummary,
+    longest: ShotSummary,
+}
+
+#[derive(Debug, Serialize)]
+struct PerFrameReport {
+    shots: usize,
+    frames: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Level1Report {
+    min: f32,
+    avg: f32,
+    max: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct Level6Report {
+    rpu: Option<LightLevels>,
+    // Estimated from Level1
+    computed: LightLevels,
+    // Whether values in RPU are close to the computed ones
+    is_close: bool,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Frames: {}", self.frames)?;
+
+        writeln!(f, "Shots: {}", self.shots.count)?;
+        writeln!(
+            f,
+            "  Shortest: {} frame(s) at frame {}",
+            self.shots.shortest.duration, self.shots.shortest.start
+        )?;
+        writeln!(
+            f,
+            "  Longest: {} frame(s) at frame {}",
+            self.shots.longest.duration, self.shots.longest.start
+        )?;
+
+        writeln!(
+            f,
+            "Frames with per-frame metadata: {} (in {} shot(s))",
+            self.per_frame_metadata.frames, self.per_frame_metadata.shots
+        )?;
+
+        writeln!(f, "Level1 (nits):")?;
+        writeln!(f, "  Min: {:.4}", self.level1.min)?;
+        writeln!(f, "  Avg: {:.4}", self.level1.avg)?;
+        writeln!(f, "  Max: {:.4}", self.level1.max)?;
+
+        if self.target_displays.is_empty() {
+            writeln!(f, "Target displays with trims: None")?;
+        } else {
+            writeln!(
+                f,
+                "Target displays with trims: {}",
+                self.target_displays.iter().join(", ")
+            )?;
+        }
+
+        writeln!(f, "Level5 aspect ratios (canvas / image):")?;
+        for change in &self.aspect_ratios {
+            writeln!(
+                f,
+                "  Frame {}: {:.4} / {:.4}",
+                change.frame, change.canvas, change.image
+            )?;
+        }
+
+        writeln!(f, "Level6:")?;
+        match &self.level6.rpu {
+            Some(rpu) => writeln!(f, "  RPU: MaxCLL {}, MaxFALL {}", rpu.max_cll, rpu.max_fall)?,
+            None => writeln!(f, "  RPU: None")?,
+        }
+        write!(
+            f,
+            "  Computed: MaxCLL {}, MaxFALL {}",
+            self.level6.computed.max_cll, self.level6.computed.max_fall
+        )?;
+
+        if !self.level6.is_close {
+            write!(
+                f,
+                "\n  Warning: values in RPU differ a lot from the computed ones."
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use dolby_vision::rpu::extension_metadata::blocks::{
+        ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel2, ExtMetadataBlockLevel5,
+    };
+    use dolby_vision::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
+
+    use super::Analyzer;
+    use crate::display::pq2l;
+    use crate::functions::{ShotOptions, ShotReader};
+    use crate::Level6;
+
+    fn level1(max_pq: u16, avg_pq: u16) -> ExtMetadataBlock {
+        ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::new(0, max_pq, avg_pq))
+    }
+
+    // Shots of 3, 1 and 2 frames, with a brighter frame in the first shot
+    // and a letterboxed second shot.
+    fn analyze() -> Analyzer {
+        let config = GenerateConfig {
+            length: 6,
+            shots: vec![
+                VideoShot {
+                    duration: 3,
+                    metadata_blocks: vec![
+                        level1(2081, 1024),
+                        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(100)),
+                    ],
+                    frame_edits: vec![ShotFrameEdit {
+                        edit_offset: 1,
+                        metadata_blocks: vec![level1(3079, 1500)],
+                    }],
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 1,
+                    metadata_blocks: vec![
+                        level1(2081, 1024),
+                        ExtMetadataBlock::Level5(ExtMetadataBlockLevel5::from_offsets(
+                            0, 0, 276, 276,
+                        )),
+                    ],
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 2,
+                    metadata_blocks: vec![level1(2081, 1024)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap().into_iter().map(Ok);
+        let shots = ShotReader::new(rpus, ShotOptions::default())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        let mut analyzer = Analyzer::default();
+        for shot in &shots {
+            analyzer.update(shot);
+        }
+
+        analyzer
+    }
+
+    #[test]
+    fn test_shots() {
+        let report = analyze().into_report(None);
+
+        assert_eq!(report.frames, 6);
+        assert_eq!(report.shots.count, 3);
+        assert_eq!(
+            (report.shots.shortest.start, report.shots.shortest.duration),
+            (3, 1)
+        );
+        assert_eq!(
+            (report.shots.longest.start, report.shots.longest.duration),
+            (0, 3)
+        );
+        // Frames are kept from the first one differing from shot
+        let per_frame = &report.per_frame_metadata;
+        assert_eq!((per_frame.shots, per_frame.frames), (1, 2));
+        assert_eq!(report.target_displays, [1]);
+
+        let frames = report
+            .aspect_ratios
+            .iter()
+            .map(|change| change.frame)
+            .collect::<Vec<_>>();
+        assert_eq!(frames, [0, 3, 4]);
+        assert!((report.aspect_ratios[1].image - 3840.0 / 1608.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_light_levels() {
+        let report = analyze().into_report(None);
+
+        let nits = |code: u16| pq2l(code as f32 / 4095.0);
+        let avg = (nits(1024) * 5.0 + nits(1500)) / 6.0;
+
+        assert_eq!(report.level1.min, 0.0);
+        assert!((report.level1.avg - avg).abs() < 1e-3);
+        assert!((report.level1.max - nits(3079)).abs() < 1e-3);
+
+        let computed = &report.level6.computed;
+        assert_eq!(computed.max_cll, nits(3079).round() as usize);
+        assert_eq!(computed.max_fall, nits(1500).round() as usize);
+        assert!(report.level6.is_close);
+
+        let level6 = |max_cll, max_fall| Level6 {
+            max_cll,
+            max_fall,
+            ..Default::default()
+        };
+
+        let report = analyze().into_report(Some(level6(1050, 0)));
+        assert!(report.level6.is_close);
+
+        let report = analyze().into_report(Some(level6(4000, 400)));
+        assert!(!report.level6.is_close);
+    }
+}
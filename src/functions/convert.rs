@@ -1,348 +1,1361 @@
 use std::collections::HashMap;
+#[cfg(feature = "cli")]
 use std::fs::File;
-use std::io::{BufWriter, Write};
+#[cfg(feature = "cli")]
+use std::io::BufWriter;
+#[cfg(feature = "cli")]
+use std::iter;
 
+#[cfg(feature = "cli")]
+use anyhow::Context;
 use anyhow::{bail, ensure, Result};
-use dolby_vision::rpu::utils::parse_rpu_file;
-use quick_xml::events::Event;
-use quick_xml::se::Serializer;
-use quick_xml::{Reader, Writer};
-use serde::Serialize;
-
-use crate::cmv40::{Characteristics, EditRate, Output, Shot, Track};
-use crate::commands::convert::ConvertArgs;
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+#[cfg(feature = "cli")]
+use itertools::Itertools;
+
+use crate::cmv40::{Characteristics, ColorEncoding, DVDynamicData, EditRate, Output, Shot, Track};
+#[cfg(feature = "cli")]
+use crate::commands::convert::{
+    ChromaFormatType, ColorSpaceType, ConvertArgs, EdlModeType, Eotf, ReelLayoutType,
+    SignalRangeType, UniqueIdType,
+};
+#[cfg(feature = "cli")]
+use crate::functions::input::Input;
+#[cfg(feature = "cli")]
+use crate::functions::{CutMismatch, EditList, EdlMode, RpuReader};
+use crate::functions::{CutThresholds, EdlCuts, IdGenerator, IdKind, IdMode};
+use crate::functions::{ShotOptions, ShotReader};
 use crate::metadata::levels::Level11;
+use crate::metadata::levels::Level2;
 use crate::metadata::levels::Level5;
+use crate::metadata::WithTid;
 use crate::MDFType::{CMV29, CMV40};
-use crate::{cmv40, display, IntoCMV29, Level254, Level6, XML_PREFIX};
+use crate::{
+    cmv29, cmv40, display, DateTime, IntoCMV29, Level6, MDFDocument, Revision, RevisionHistory,
+    UUIDv4, UHD_CANVAS,
+};
+#[cfg(feature = "cli")]
+use crate::{ChromaFormat, ChromaFormatEnum, ColorSpaceEnum, EncodingEnum, SignalRangeEnum};
+
+/// Source of MaxCLL and MaxFALL in track-level Level 6.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Level6Mode {
+    /// Zero values, as ST.2086 metadata is not required for a Dolby Vision deliverable.
+    #[default]
+    Empty,
+    /// Values from RPU, if possible.
+    FromRpu,
+    /// Values estimated from Level 1 of all frames.
+    Estimated,
+}
+
+/// Layout of multiple reels in output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReelLayout {
+    /// A single output with all reels conformed to one timeline.
+    #[default]
+    Conformed,
+    /// An output for each reel.
+    Outputs,
+}
+
+/// RPUs of a reel in decode order.
+#[derive(Debug, Clone)]
+pub struct Reel<I> {
+    pub rpus: I,
+    // Name of output for the reel, when reels are not conformed
+    pub name: Option<String>,
+    // First frame of the reel in timeline, or `offset` in options when None.
+    // Conformed reels follow the previous one by default.
+    pub record_in: Option<usize>,
+}
+
+impl<I> Reel<I> {
+    pub fn new(rpus: I) -> Self {
+        Self {
+            rpus,
+            name: None,
+            record_in: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    pub canvas: (usize, usize),
+    pub edit_rate: EditRate,
+    // Number of frames to be skipped from start
+    pub skip: usize,
+    // Number of frames to be parsed, None for all frames
+    pub count: Option<usize>,
+    // Number of frames to be added to the index, and first frame of the first reel in timeline
+    pub offset: usize,
+    pub keep_offset: bool,
+    pub drop_per_frame: bool,
+    pub level6: Level6Mode,
+    // Determined by input when None
+    pub mastering_display: Option<display::Characteristics>,
+    pub color_encoding: ColorEncoding,
+    pub id_mode: IdMode,
+    // Seconds since Unix epoch for revision history, `SOURCE_DATE_EPOCH` or current time when None
+    pub timestamp: Option<i64>,
+    pub reel_layout: ReelLayout,
+    // Detect cuts from metadata of per-frame RPU, when set
+    pub cut_detection: Option<CutThresholds>,
+    // Shot boundaries from an EDL, in timeline of all reels
+    pub edl_cuts: Option<EdlCuts>,
+    // Target display IDs to be kept, all when None
+    pub keep_targets: Option<Vec<usize>>,
+    // Target display IDs to be dropped with their trims
+    pub drop_targets: Vec<usize>,
+    // Predefined or user-defined target display IDs to be added with identity trims
+    pub add_targets: Vec<usize>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            canvas: UHD_CANVAS,
+            edit_rate: EditRate::default(),
+            skip: 0,
+            count: None,
+            offset: 0,
+            keep_offset: false,
+            drop_per_frame: false,
+            level6: Level6Mode::default(),
+            mastering_display: None,
+            color_encoding: ColorEncoding::default(),
+            id_mode: IdMode::default(),
+            timestamp: None,
+            reel_layout: ReelLayout::default(),
+            cut_detection: None,
+            edl_cuts: None,
+            keep_targets: None,
+            drop_targets: Vec::new(),
+            add_targets: Vec::new(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.count != Some(0), "Invalid specified frame count.");
+        ensure!(
+            self.skip < self.count.unwrap_or(usize::MAX),
+            "Invalid skip count."
+        );
+        ensure!(
+            self.canvas.0 != 0 && self.canvas.1 != 0,
+            "Invalid canvas size."
+        );
+
+        if let Some(mastering_display) = &self.mastering_display {
+            ensure!(
+                mastering_display.peak_brightness > 0
+                    && mastering_display.minimum_brightness >= 0.0
+                    && mastering_display.minimum_brightness
+                        < mastering_display.peak_brightness as f32,
+                "Invalid luminance range of mastering display."
+            );
+        }
+
+        if let Some(timestamp) = self.timestamp {
+            ensure!(
+                DateTime::from_timestamp(timestamp).is_some(),
+                "Invalid timestamp."
+            );
+        }
+
+        if let Some(bit_depth) = self.color_encoding.bit_depth {
+            ensure!(
+                (8..=16).contains(&bit_depth),
+                "Invalid bit depth of color encoding."
+            );
+        }
+
+        if let Some(cut_detection) = &self.cut_detection {
+            cut_detection.validate()?;
+        }
+
+        for &id in &self.add_targets {
+            ensure!(
+                display::Characteristics::get_target_with_id(id).is_some(),
+                "Unknown target display ID {id}."
+            );
+            ensure!(
+                self.is_target_kept(id),
+                "Target display {id} can't be added and dropped."
+            );
+        }
+
+        self.edit_rate.validate()
+    }
+
+    pub fn is_target_kept(&self, id: usize) -> bool {
+        self.keep_targets
+            .as_ref()
+            .map_or(true, |targets| targets.contains(&id))
+            && !self.drop_targets.contains(&id)
+    }
+}
+
+impl From<&ConvertOptions> for ShotOptions {
+    fn from(options: &ConvertOptions) -> Self {
+        Self {
+            canvas: options.canvas,
+            skip: options.skip,
+            count: options.count,
+            offset: options.offset,
+            keep_offset: options.keep_offset,
+            drop_per_frame: options.drop_per_frame,
+            cut_detection: options.cut_detection,
+        }
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct Converter {
-    frame_index: usize,
-    // scene_count: usize,
-    invalid_frame_count: usize,
-    first_valid_frame_index: Option<usize>,
-    shots: Option<Vec<Shot>>,
-    last_shot: Shot,
-    track: Track,
+    options: ConvertOptions,
+    // Shot-level metadata -> number of frames
+    level5_map: HashMap<Option<Level5>, usize>,
+    level11_map: HashMap<Option<Level11>, usize>,
+    // Estimated from Level 1
+    level6: Level6,
+    reels: Vec<ReelInfo>,
     level5: Option<Level5>,
     level11: Option<Level11>,
-    level254: Option<Level254>,
+    ids: IdGenerator,
+}
+
+#[derive(Debug, Default)]
+struct ReelInfo {
+    name: Option<String>,
+    record_in: usize,
+    record_end: usize,
+    // Global information from the first valid frame
+    track: Option<Track>,
+    targets: Vec<Characteristics>,
+    // Unique ID of CM v2.9 source
+    source_id: UUIDv4,
 }
 
 impl Converter {
+    fn new(options: ConvertOptions) -> Self {
+        Self {
+            ids: IdGenerator::new(options.id_mode, options.edit_rate),
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Convert RPUs in decode order to DolbyLabsMDF.
+    /// All shots are kept in memory, the output version is determined by input.
+    pub fn convert_rpus<I>(rpus: I, options: ConvertOptions) -> Result<MDFDocument>
+    where
+        I: IntoIterator<Item = DoviRpu>,
+    {
+        Self::convert_reels(vec![Reel::new(rpus)], options)
+    }
+
+    /// Convert RPUs of multiple reels to DolbyLabsMDF, in the layout set in options.
+    /// Target displays and mastering display are merged from all reels.
+    pub fn convert_reels<I>(reels: Vec<Reel<I>>, options: ConvertOptions) -> Result<MDFDocument>
+    where
+        I: IntoIterator<Item = DoviRpu>,
+    {
+        options.validate()?;
+        // Frames would be skipped in each reel instead of the timeline
+        ensure!(
+            reels.len() <= 1 || (options.skip == 0 && options.count.is_none()),
+            "Skip and count of frames can't be used with multiple reels."
+        );
+
+        let mut converter = Converter::new(options);
+        let mut reel_shots = Vec::with_capacity(reels.len());
+
+        for reel in reels {
+            let shot_options = converter.begin_reel(reel.name, reel.record_in)?;
+            let rpus = reel.rpus.into_iter().map(Ok::<_, anyhow::Error>);
+            let mut shot_reader = converter.shot_reader(rpus, shot_options);
+
+            let mut shots = Vec::new();
+            for shot in shot_reader.by_ref() {
+                let shot = shot?;
+                converter.update(&shot);
+                shots.push(shot);
+            }
+
+            converter.end_reel(&mut shot_reader)?;
+            reel_shots.push(shots);
+        }
+
+        let mut md = converter.build_document()?;
+        let tracks_shots = converter.group_reels(reel_shots).into_iter().map(|reels| {
+            reels
+                .into_iter()
+                .flat_map(|(reel, shots)| shots.into_iter().map(move |shot| (reel, shot)))
+                .map(|(reel, shot)| (reel, converter.trim_shot(shot, reel)))
+        });
+
+        if Self::is_cmv29(&md) {
+            let mut md = converter.cmv29_document(md);
+
+            let tracks = md
+                .outputs
+                .iter_mut()
+                .flat_map(|outputs| outputs.outputs.iter_mut().flatten())
+                .filter_map(|output| output.video.tracks.first_mut());
+
+            for (track, shots) in tracks.zip(tracks_shots) {
+                track.shots = Some(
+                    shots
+                        .map(|(reel, shot)| converter.cmv29_shot(shot, reel))
+                        .collect(),
+                );
+            }
+
+            Ok(MDFDocument::CMV29(md))
+        } else {
+            let tracks = md
+                .outputs
+                .outputs
+                .iter_mut()
+                .filter_map(|output| output.video.tracks.first_mut());
+
+            for (track, shots) in tracks.zip(tracks_shots) {
+                track.shots = Some(shots.map(|(_, shot)| shot).collect());
+            }
+
+            Ok(MDFDocument::CMV40(md))
+        }
+    }
+
+    #[cfg(feature = "cli")]
     pub fn convert(args: ConvertArgs) -> Result<()> {
-        let input = match args.input {
-            Some(input) => input,
+        let input = match &args.input {
+            Some(input) => input.clone(),
             None => bail!("No input file provided."),
         };
 
-        ensure!(args.count != Some(0), "Invalid specified frame count.");
         ensure!(
-            args.skip < args.count.unwrap_or(usize::MAX),
-            "Invalid skip count."
+            args.rate.len() <= 2,
+            "Invalid frame rate. Use '/' as delimiter if needed, like 24 or 24000/1001"
         );
 
-        ensure!(
-            args.size.len() == 2,
-            "Invalid canvas size. Use 'x' as delimiter, like 3840x2160"
-        );
-        ensure!(
-            args.size[0] != 0 && args.size[1] != 0,
-            "Invalid canvas size."
-        );
+        let inputs = iter::once(input)
+            .chain(args.reel.clone())
+            .collect::<Vec<_>>();
 
         ensure!(
-            args.rate.len() <= 2,
-            "Invalid frame rate. Use '/' as delimiter if needed, like 24 or 24000/1001"
+            args.reel_offset.len() <= inputs.len(),
+            "Too many reel offsets, {} reel(s) found.",
+            inputs.len()
         );
 
-        let canvas = Converter::parse_canvas_ar(args.size)?;
+        let mastering_display = Self::parse_mastering_display(&args)?;
+        let color_encoding = Self::parse_color_encoding(&args)?;
 
-        println!("Parsing RPU file...");
+        let level6 = if args.estimate_level6 {
+            Level6Mode::Estimated
+        } else if args.use_level6 {
+            Level6Mode::FromRpu
+        } else {
+            Level6Mode::Empty
+        };
 
-        let rpus = parse_rpu_file(input)?;
+        let edit_rate = EditRate::from(args.rate.clone());
+        edit_rate.validate()?;
 
-        let mut count = if let Some(count) = args.count {
-            if count + args.skip > rpus.len() {
-                println!("Specified frame count exceeds the end.");
-                rpus.len()
-            } else {
-                count
+        // EDL with the record timecode of the first frame in timeline
+        let edit_list = match &args.edl {
+            Some(path) => {
+                let edit_list = EditList::from_file(path, edit_rate)?;
+                let start = match &args.edl_start {
+                    Some(timecode) => edit_list.format.parse(timecode)?,
+                    None => edit_list.start(),
+                };
+
+                Some((edit_list, start))
             }
-        } else {
-            rpus.len()
+            None => None,
         };
 
-        let mut converter = Converter::default();
+        let options = ConvertOptions {
+            canvas: Input::canvas(&args.size)?,
+            edit_rate,
+            skip: args.skip,
+            count: args.count,
+            offset: args.offset,
+            keep_offset: args.keep_offset,
+            drop_per_frame: args.drop_per_frame,
+            level6,
+            mastering_display,
+            color_encoding,
+            id_mode: args.unique_id.into(),
+            timestamp: args.timestamp,
+            reel_layout: args.reel_layout.into(),
+            cut_detection: CutThresholds::from_args(&args.cut_detection),
+            edl_cuts: edit_list.as_ref().map(|(edit_list, start)| {
+                edit_list.cuts(*start, args.offset, args.edl_mode.into())
+            }),
+            keep_targets: args.keep_targets.clone(),
+            drop_targets: args.drop_targets.clone(),
+            add_targets: args.add_targets.clone(),
+        };
+        options.validate()?;
 
-        let edit_rate = EditRate::from(args.rate);
-        edit_rate.validate()?;
+        let mut converter = Converter::new(options);
 
-        println!("Converting RPU file...");
+        // The first pass only collects track-level metadata, shots are dropped once counted.
+        for (reel, input) in inputs.iter().enumerate() {
+            if inputs.len() > 1 {
+                println!("Parsing RPU file of reel {}...", reel + 1);
+            } else {
+                println!("Parsing RPU file...");
+            }
 
-        let mut targets_map = HashMap::new();
-
-        // Parse shot-based and frame-based metadata
-        for rpu in rpus {
-            if count > 0 {
-                if let Some(ref vdr) = rpu.vdr_dm_data {
-                    if converter.frame_index >= args.skip {
-                        let frame_index = converter.frame_index - args.skip + args.offset;
-                        // TODO: Use real offset if first valid frame index is not 0?
-
-                        if converter.first_valid_frame_index.is_none()
-                            || vdr.scene_refresh_flag == 1
-                        {
-                            match &mut converter.shots {
-                                // Initialize
-                                None => {
-                                    converter.shots = Some(Vec::new());
-                                }
-                                Some(shots) => {
-                                    shots.push(converter.last_shot.clone());
-                                }
-                            }
-
-                            converter.last_shot = Shot::with_canvas(vdr, canvas);
-                            converter.last_shot.update_record(Some(frame_index), None);
-
-                            // FIXME: Assume input rpu file is valid,
-                            // so only use the first valid frame to get global information we need
-                            if converter.first_valid_frame_index.is_none() {
-                                if converter.invalid_frame_count > 0 {
-                                    println!(
-                                        "Skipped {} invalid frame(s) from start.",
-                                        converter.invalid_frame_count
-                                    );
-                                    converter.invalid_frame_count = 0;
-                                }
-                                if args.keep_offset {
-                                    converter.last_shot.update_record(None, Some(args.skip));
-                                    converter.frame_index += args.skip;
-                                }
-                                converter.first_valid_frame_index = Some(frame_index);
-                                converter.track = Track::with_single_vdr(vdr);
-
-                                if !args.use_level6 {
-                                    converter.track.level6 = Some(Level6::default());
-                                }
-
-                                converter
-                                    .level254
-                                    .clone_from(&converter.track.plugin_node.level254);
-
-                                converter.track.edit_rate = if converter.level254.is_none() {
-                                    CMV29(edit_rate)
-                                } else {
-                                    CMV40(edit_rate)
-                                };
-                            };
-                        } else {
-                            converter.last_shot.update_record(None, None);
-                            if !args.drop_per_frame {
-                                converter
-                                    .last_shot
-                                    .append_metadata(&Shot::with_canvas(vdr, canvas));
-                            }
-                        }
-
-                        if let Some(d) = display::Characteristics::get_targets(vdr) {
-                            d.iter().for_each(|c| {
-                                let target = Characteristics::from(c.clone());
-                                targets_map.entry(target.id).or_insert(target);
-                            })
-                        }
-
-                        count -= 1;
-                    }
-
-                    converter.frame_index += 1;
-                } else {
-                    // Should not happen
-                    if converter.first_valid_frame_index.is_some() {
-                        // Invalid RPU in the middle of sequence, use last valid frame
-                        converter.frame_index += 1;
-                        converter.last_shot.update_record(None, None);
-                        if let Some(ref mut frames) = converter.last_shot.frames {
-                            if let Some(frame) = frames.pop() {
-                                frames.push(frame.clone());
-                                frames.push(frame);
-                            }
-                        }
-
-                        count -= 1;
-                    }
-
-                    converter.invalid_frame_count += 1;
-                }
+            let name = input
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+            let shot_options = converter.begin_reel(name, args.reel_offset.get(reel).copied())?;
+            let mut shot_reader = converter.shot_reader(RpuReader::open(input)?, shot_options);
+
+            for shot in shot_reader.by_ref() {
+                converter.update(&shot?);
+            }
+
+            shot_reader.report();
+            if let Some((edit_list, start)) = &edit_list {
+                let mismatches = shot_reader.take_cut_mismatches();
+                Self::report_cut_mismatches(&mismatches, edit_list, *start, args.offset);
+            }
+            converter.end_reel(&mut shot_reader)?;
+        }
+
+        if let Some(level6) = converter.rpu_level6() {
+            if !level6.is_close_to(&converter.level6) {
+                println!(
+                    "Warning: MaxCLL {} and MaxFALL {} in RPU differ a lot from the estimated {} and {}.",
+                    level6.max_cll,
+                    level6.max_fall,
+                    converter.level6.max_cll,
+                    converter.level6.max_fall
+                );
             }
         }
 
-        if converter.invalid_frame_count > 0 {
+        if converter.options.mastering_display.is_none()
+            && converter
+                .tracks()
+                .map(|track| track.plugin_node.dv_global_data.mastering_display.id)
+                .unique()
+                .count()
+                > 1
+        {
+            println!("Mastering displays differ between reels, using the brightest one.");
+        }
+
+        let md = converter.build_document()?;
+        let is_cmv29 = Self::is_cmv29(&md);
+
+        let output = if let Some(output) = args.output {
+            output
+        } else {
+            println!("No output file provided, writing to metadata.xml at current path...");
+            "./metadata.xml".into()
+        };
+
+        let writer = BufWriter::new(File::create(output)?);
+
+        println!("Converting RPU file...");
+
+        // The second pass writes each shot as soon as it's closed.
+        let readers = inputs
+            .iter()
+            .map(RpuReader::open)
+            .collect::<Result<Vec<_>>>()?;
+        let converter = &converter;
+        let tracks_shots = converter.group_reels(readers).into_iter().map(|reels| {
+            reels.into_iter().flat_map(move |(reel, reader)| {
+                converter
+                    .shot_reader(reader, converter.reel_options(reel))
+                    .map(move |shot| shot.map(|shot| (reel, converter.trim_shot(shot, reel))))
+            })
+        });
+
+        if is_cmv29 {
+            println!("CM v2.9 RPU found, saving as v2.0.5 XML...");
+
+            let md = converter.cmv29_document(md);
+            let tracks_shots = tracks_shots.map(|shots| {
+                shots.map(|shot| shot.map(|(reel, shot)| converter.cmv29_shot(shot, reel)))
+            });
+
+            MDFDocument::CMV29(md).write_xml_tracks(writer, tracks_shots)?;
+        } else {
+            let document = MDFDocument::CMV40(md);
             println!(
-                "Skipped {} invalid frame(s) in the middle, replaced with previous metadata.",
-                converter.invalid_frame_count
+                "CM v4.0 RPU found, saving as v{} XML...",
+                document.version()
             );
-        }
 
-        // Push remained shot
-        if converter.shots.is_none() {
-            converter.shots = Some(Vec::new());
+            let tracks_shots =
+                tracks_shots.map(|shots| shots.map(|shot| shot.map(|(_, shot)| shot)));
+
+            document.write_xml_tracks(writer, tracks_shots)?;
         }
 
-        if let Some(ref mut shots) = converter.shots {
-            shots.push(converter.last_shot.clone());
+        Ok(())
+    }
 
-            let mut targets = targets_map.values().cloned().collect::<Vec<_>>();
-            if !targets.is_empty() {
-                targets.sort_by_key(|c| c.id);
-                converter.track.plugin_node.dv_global_data.target_displays = Some(targets);
+    /// Start a reel with its first frame in timeline,
+    /// returns options to read shots of the reel.
+    fn begin_reel(
+        &mut self,
+        name: Option<String>,
+        record_in: Option<usize>,
+    ) -> Result<ShotOptions> {
+        let record_in = match self.options.reel_layout {
+            ReelLayout::Conformed => {
+                let previous_end = self.reels.last().map(|reel| reel.record_end);
+                let record_in = record_in.or(previous_end).unwrap_or(self.options.offset);
+
+                ensure!(
+                    previous_end.map_or(true, |end| record_in >= end),
+                    "Reel {} overlaps the previous reel in timeline.",
+                    self.reels.len() + 1
+                );
+
+                record_in
             }
+            // Each reel has its own timeline
+            ReelLayout::Outputs => {
+                ensure!(
+                    self.options.edl_cuts.is_none() || self.reels.is_empty(),
+                    "EDL can only be used with reels conformed to one timeline."
+                );
 
-            let mut level5_map = HashMap::new();
-            let mut level11_map = HashMap::new();
+                record_in.unwrap_or(self.options.offset)
+            }
+        };
 
-            shots.iter().for_each(|shot| {
-                let mut shot_level_duration = shot.record.duration;
+        self.reels.push(ReelInfo {
+            name,
+            record_in,
+            record_end: record_in,
+            ..Default::default()
+        });
 
-                if let Some(ref frames) = shot.frames {
-                    shot_level_duration -= frames.len();
+        Ok(self.reel_options(self.reels.len() - 1))
+    }
 
-                    frames.iter().for_each(|frame| {
-                        *level5_map
-                            .entry(&frame.plugin_node.dv_dynamic_data.level5)
-                            .or_insert(0) += 1_usize;
+    /// Keep global information of the current reel, after all shots passed to `update`.
+    fn end_reel<I>(&mut self, shot_reader: &mut ShotReader<I>) -> Result<()>
+    where
+        I: Iterator<Item = Result<DoviRpu>>,
+    {
+        let count = self.reels.len();
+        let Some(reel) = self.reels.last_mut() else {
+            bail!("No reel is started.");
+        };
 
-                        *level11_map.entry(&frame.plugin_node.level11).or_insert(0) += 1_usize;
-                    });
-                }
+        let Some(track) = shot_reader.take_track() else {
+            if count > 1 {
+                bail!("No valid RPU found in reel {count}.");
+            }
 
-                *level5_map
-                    .entry(&shot.plugin_node.dv_dynamic_data.level5)
-                    .or_insert(0) += shot_level_duration;
+            bail!("No valid RPU found.");
+        };
 
-                *level11_map.entry(&shot.plugin_node.level11).or_insert(0) += shot_level_duration;
-            });
+        reel.track = Some(track);
+        reel.targets = shot_reader.take_targets();
 
-            // converter.level5 = Some(Self::get_global_ar(level5_map, canvas));
-            converter.level5 =
-                Self::get_common(level5_map).or_else(|| Some(Level5::with_canvas(None, canvas)));
+        Ok(())
+    }
 
-            // Choose the most common level11 as track-level metadata,
-            converter.level11 = Self::get_common(level11_map);
+    fn shot_reader<I>(&self, rpus: I, options: ShotOptions) -> ShotReader<I>
+    where
+        I: Iterator<Item = Result<DoviRpu>>,
+    {
+        let shot_reader = ShotReader::new(rpus, options);
 
-            // and remove them in shot-level.
-            shots.iter_mut().for_each(|shot| {
-                let shot_level5 = shot.plugin_node.dv_dynamic_data.level5.clone();
-                if shot_level5 == converter.level5 {
-                    shot.plugin_node.dv_dynamic_data.level5 = None;
-                };
+        match &self.options.edl_cuts {
+            Some(cuts) => shot_reader.with_edl_cuts(cuts.clone()),
+            None => shot_reader,
+        }
+    }
 
-                shot.plugin_node.level11 = None;
+    fn reel_options(&self, reel: usize) -> ShotOptions {
+        let mut options = ShotOptions::from(&self.options);
+        options.offset = self.reels[reel].record_in;
 
-                // Level 5 can not exist in per-frame metadata anyway,
-                // but it's not our responsibility to validate it here.
-                // TODO: test case
-                if let Some(ref mut frames) = shot.frames {
-                    frames.iter_mut().for_each(|frame| {
-                        let plugin_node = &mut frame.plugin_node;
-                        let dv_dynamic_data = &mut plugin_node.dv_dynamic_data;
+        options
+    }
 
-                        let frame_level5 = dv_dynamic_data.level5.clone();
-                        if frame_level5 == converter.level5 || frame_level5 == shot_level5 {
-                            dv_dynamic_data.level5 = None;
-                        }
+    /// Group items of each reel by track in output, with index of reel.
+    fn group_reels<T>(&self, items: Vec<T>) -> Vec<Vec<(usize, T)>> {
+        let items = items.into_iter().enumerate();
 
-                        plugin_node.level11 = None;
-                    })
+        match self.options.reel_layout {
+            ReelLayout::Conformed => vec![items.collect()],
+            ReelLayout::Outputs => items.map(|item| vec![item]).collect(),
+        }
+    }
+
+    fn tracks(&self) -> impl Iterator<Item = &Track> {
+        self.reels.iter().filter_map(|reel| reel.track.as_ref())
+    }
+
+    /// Maximum of Level 6 in all reels.
+    fn rpu_level6(&self) -> Option<Level6> {
+        self.tracks()
+            .filter_map(|track| track.level6.clone())
+            .reduce(|a, b| Level6 {
+                max_cll: a.max_cll.max(b.max_cll),
+                max_fall: a.max_fall.max(b.max_fall),
+                ..a
+            })
+    }
+
+    /// Track from the first reel, with target displays of all reels
+    /// and the brightest mastering display.
+    fn merge_tracks(&mut self) -> Result<Track> {
+        let level6 = self.rpu_level6();
+
+        let mut tracks = self.reels.iter_mut().filter_map(|reel| reel.track.take());
+        let Some(mut track) = tracks.next() else {
+            bail!("No valid RPU found.");
+        };
+
+        for other in tracks {
+            ensure!(
+                other.plugin_node.level254.is_none() == track.plugin_node.level254.is_none(),
+                "Reels with different CM versions can't be merged."
+            );
+
+            let mastering_display = other.plugin_node.dv_global_data.mastering_display;
+            if mastering_display.peak_brightness
+                > track
+                    .plugin_node
+                    .dv_global_data
+                    .mastering_display
+                    .peak_brightness
+            {
+                track.plugin_node.dv_global_data.mastering_display = mastering_display;
+            }
+        }
+
+        track.level6 = level6;
+
+        Ok(track)
+    }
+
+    /// Target displays found in all reels, sorted by ID.
+    /// Dropped targets are removed, and added targets are included.
+    fn merge_targets(&mut self) -> Vec<Characteristics> {
+        let mut targets: Vec<Characteristics> = Vec::new();
+
+        for target in self
+            .reels
+            .iter_mut()
+            .flat_map(|reel| std::mem::take(&mut reel.targets))
+        {
+            if targets.iter().all(|t| t.id != target.id) {
+                targets.push(target);
+            }
+        }
+
+        targets.retain(|target| self.options.is_target_kept(target.id));
+
+        for &id in &self.options.add_targets {
+            if targets.iter().all(|t| t.id != id) {
+                if let Some(target) = display::Characteristics::get_target_with_id(id) {
+                    targets.push(target.into());
                 }
-            });
+            }
         }
 
-        converter.track.shots = converter.shots;
-        converter.track.plugin_node.level11 = converter.level11;
+        targets.sort_by_key(|c| c.id);
 
-        let output = Output::with_level5(converter.track, converter.level5);
+        targets
+    }
 
-        let md = cmv40::DolbyLabsMDF::with_single_output(output)?;
+    /// ID of an element in a reel, the reel is ignored when reels are conformed.
+    fn get_id(&self, kind: IdKind, reel: usize, index: usize) -> UUIDv4 {
+        match self.options.reel_layout {
+            ReelLayout::Conformed => self.ids.get(kind, index),
+            ReelLayout::Outputs => self.ids.get_in_reel(kind, reel, index),
+        }
+    }
 
-        let mut serializer_buffer = String::new();
-        let ser = Serializer::new(&mut serializer_buffer);
+    /// Document with track-level metadata, from all shots passed to `update`.
+    /// Shots are not included.
+    fn build_document(&mut self) -> Result<cmv40::DolbyLabsMDF> {
+        let mut track = self.merge_tracks()?;
 
-        if converter.level254.is_none() {
-            println!("CM v2.9 RPU found, saving as v2.0.5 XML...");
-            md.into_cmv29().serialize(ser)?;
-        } else {
-            println!("CM v4.0 RPU found, saving as v{} XML...", md.version);
-            md.serialize(ser)?;
+        match self.options.level6 {
+            Level6Mode::Empty => track.level6 = Some(Level6::default()),
+            Level6Mode::FromRpu => {}
+            Level6Mode::Estimated => track.level6 = Some(self.level6.clone()),
         }
 
-        let output = if let Some(output) = args.output {
-            output
+        track.color_encoding = self.options.color_encoding.clone();
+
+        let edit_rate = self.options.edit_rate;
+        track.edit_rate = if track.plugin_node.level254.is_none() {
+            CMV29(edit_rate)
         } else {
-            println!("No output file provided, writing to metadata.xml at current path...");
-            "./metadata.xml".into()
+            CMV40(edit_rate)
         };
 
-        let mut output_buffer = BufWriter::new(File::create(output)?);
-        write!(
-            output_buffer,
-            "{}{}",
-            XML_PREFIX,
-            Self::prettify_xml(serializer_buffer)
-        )?;
+        if let Some(mastering_display) = &self.options.mastering_display {
+            track.plugin_node.dv_global_data.mastering_display = mastering_display.clone().into();
+        }
 
-        Ok(())
+        let targets = self.merge_targets();
+        if !targets.is_empty() {
+            track.plugin_node.dv_global_data.target_displays = Some(targets);
+        }
+
+        self.level5 = Self::get_common(&self.level5_map)
+            .or_else(|| Some(Level5::with_canvas(None, self.options.canvas)));
+
+        // Choose the most common level11 as track-level metadata,
+        // and remove the identical ones in shot-level.
+        // Track has Level 11 if any shot has it, so the version is determined by track.
+        self.level11 = Self::get_common(&self.level11_map);
+        track.plugin_node.level11.clone_from(&self.level11);
+
+        for i in 0..self.reels.len() {
+            self.reels[i].source_id = self.get_id(IdKind::Source, i, self.reels[i].record_in);
+        }
+
+        let outputs = match self.options.reel_layout {
+            ReelLayout::Conformed => {
+                track.unique_id = self.ids.get(IdKind::Track, 0);
+
+                let mut output = Output::with_level5(track, self.level5.clone());
+                output.unique_id = self.ids.get(IdKind::Output, 0);
+
+                vec![output]
+            }
+            ReelLayout::Outputs => self
+                .reels
+                .iter()
+                .enumerate()
+                .map(|(i, reel)| {
+                    let mut track = track.clone();
+                    track.unique_id = self.ids.get_in_reel(IdKind::Track, i, 0);
+
+                    let mut output = Output::with_level5(track, self.level5.clone());
+                    output.unique_id = self.ids.get_in_reel(IdKind::Output, i, 0);
+                    output.composition_name = reel
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("Reel {}", i + 1));
+
+                    output
+                })
+                .collect(),
+        };
+
+        let mut md = cmv40::DolbyLabsMDF::with_outputs(outputs)?;
+
+        if let Some(date_time) = self.options.timestamp.and_then(DateTime::from_timestamp) {
+            md.revision_history = Some(RevisionHistory {
+                revisions: Some(vec![Revision {
+                    date_time,
+                    ..Default::default()
+                }]),
+            });
+        }
+
+        Ok(md)
+    }
+
+    /// CM v2.9 document with a source for each reel.
+    fn cmv29_document(&self, md: cmv40::DolbyLabsMDF) -> cmv29::DolbyLabsMDF {
+        let mut md = md.into_cmv29();
+        md.source_list = Some(cmv29::SourceList {
+            sources: Some(
+                self.reels
+                    .iter()
+                    .map(|reel| {
+                        cmv29::Source::with_range(
+                            reel.source_id.clone(),
+                            reel.record_in,
+                            reel.record_end - reel.record_in,
+                        )
+                    })
+                    .collect(),
+            ),
+        });
+
+        md
     }
 
-    /// None: Standard UHD
-    fn parse_canvas_ar(vec: Vec<usize>) -> Result<(usize, usize)> {
+    fn cmv29_shot(&self, shot: Shot, reel: usize) -> cmv29::Shot {
+        let mut shot = shot.into_cmv29();
+        shot.source.parent_id = self.reels[reel].source_id.clone();
+        shot.source.in_ = shot.record.in_;
+
+        for frame in shot.frames.iter_mut().flatten() {
+            frame.unique_id = self.get_id(IdKind::Frame, reel, shot.record.in_ + frame.edit_offset);
+        }
+
+        shot
+    }
+
+    fn is_cmv29(md: &cmv40::DolbyLabsMDF) -> bool {
+        matches!(
+            md.outputs.outputs.first().and_then(|output| output.video.tracks.first()),
+            Some(track) if track.plugin_node.level254.is_none()
+        )
+    }
+
+    /// Record timecode in EDL of each frame where cuts disagree,
+    /// `start` is the record timecode of the frame `offset` in timeline.
+    #[cfg(feature = "cli")]
+    fn report_cut_mismatches(
+        mismatches: &[CutMismatch],
+        edit_list: &EditList,
+        start: usize,
+        offset: usize,
+    ) {
+        if mismatches.is_empty() {
+            println!("Cuts in EDL and RPU match.");
+            return;
+        }
+
+        println!(
+            "Cuts in EDL and RPU disagree at {} frame(s):",
+            mismatches.len()
+        );
+
+        for mismatch in mismatches {
+            let (frame, source) = match *mismatch {
+                CutMismatch::MissingInRpu(frame) => (frame, "EDL"),
+                CutMismatch::MissingInEdl(frame) => (frame, "RPU"),
+            };
+
+            let timecode = edit_list.format.timecode(frame + start - offset);
+            println!("  {timecode} (frame {frame}): cut in {source} only");
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    fn parse_mastering_display(args: &ConvertArgs) -> Result<Option<display::Characteristics>> {
+        if let Some(id) = args.mastering_display {
+            return display::Characteristics::get_source(id)
+                .with_context(|| format!("Unknown mastering display ID {id}."))
+                .map(Some);
+        }
+
+        let Some(peak_brightness) = args.mastering_peak else {
+            return Ok(None);
+        };
+
+        let mut primaries = display::Characteristics::default_source().primaries;
+
+        if let Some(values) = &args.mastering_primaries {
+            ensure!(
+                values.len() == 6,
+                "Invalid primaries of mastering display. Format: Rx,Ry,Gx,Gy,Bx,By"
+            );
+
+            primaries.red = display::Chromaticity([values[0], values[1]]);
+            primaries.green = display::Chromaticity([values[2], values[3]]);
+            primaries.blue = display::Chromaticity([values[4], values[5]]);
+        }
+
         ensure!(
-            vec.len() == 2,
-            "Invalid canvas size. Use 'x' as delimiter, like 3840x2160"
+            args.mastering_white_point.len() == 2,
+            "Invalid white point of mastering display. Format: x,y"
         );
-        ensure!(vec[0] != 0 && vec[1] != 0, "Invalid canvas size.");
-        Ok((vec[0], vec[1]))
+        primaries.white_point =
+            display::Chromaticity([args.mastering_white_point[0], args.mastering_white_point[1]]);
+
+        Ok(Some(display::Characteristics::custom_source(
+            primaries,
+            peak_brightness,
+            args.mastering_min,
+        )))
+    }
+
+    #[cfg(feature = "cli")]
+    fn parse_color_encoding(args: &ConvertArgs) -> Result<ColorEncoding> {
+        let primaries = display::Primaries::get_index_primary(args.encoding_primaries, false)
+            .with_context(|| format!("Unknown primaries index {}.", args.encoding_primaries))?;
+
+        let mut color_encoding = ColorEncoding::with_primaries(primaries, args.eotf.into());
+
+        color_encoding.bit_depth = args.bit_depth;
+        color_encoding.color_space.color_space = args.color_space.into();
+        color_encoding.chroma_format = args.chroma_format.map(|c| ChromaFormat {
+            chroma_format: c.into(),
+        });
+        color_encoding.signal_range.signal_range = args.signal_range.into();
+
+        Ok(color_encoding)
+    }
+
+    fn update(&mut self, shot: &Shot) {
+        let mut shot_level_duration = shot.record.duration;
+
+        // Random ID of shot is not included
+        self.ids
+            .update(&(&shot.record, &shot.plugin_node, &shot.frames));
+
+        self.level6
+            .update_with_level1(&shot.plugin_node.dv_dynamic_data.level1);
+
+        if let Some(ref frames) = shot.frames {
+            shot_level_duration -= frames.len();
+
+            frames.iter().for_each(|frame| {
+                self.level6
+                    .update_with_level1(&frame.plugin_node.dv_dynamic_data.level1);
+
+                *self
+                    .level5_map
+                    .entry(frame.plugin_node.dv_dynamic_data.level5.clone())
+                    .or_insert(0) += 1;
+
+                // Level 11 is only in shot-level metadata
+                let level11 = frame.plugin_node.level11.as_ref();
+                *self
+                    .level11_map
+                    .entry(level11.or(shot.plugin_node.level11.as_ref()).cloned())
+                    .or_insert(0) += 1;
+            });
+        }
+
+        *self
+            .level5_map
+            .entry(shot.plugin_node.dv_dynamic_data.level5.clone())
+            .or_insert(0) += shot_level_duration;
+
+        *self
+            .level11_map
+            .entry(shot.plugin_node.level11.clone())
+            .or_insert(0) += shot_level_duration;
+
+        if let Some(reel) = self.reels.last_mut() {
+            reel.record_end = reel.record_end.max(shot.record.in_ + shot.record.duration);
+        }
     }
 
-    fn get_common<K, V>(map: HashMap<&Option<K>, V>) -> Option<K>
+    /// Remove metadata which is already in track-level.
+    fn trim_shot(&self, shot: Shot, reel: usize) -> Shot {
+        let mut shot = shot;
+
+        shot.unique_id = self.get_id(IdKind::Shot, reel, shot.record.in_);
+
+        let shot_level5 = shot.plugin_node.dv_dynamic_data.level5.clone();
+        if shot_level5 == self.level5 {
+            shot.plugin_node.dv_dynamic_data.level5 = None;
+        };
+
+        if shot.plugin_node.level11 == self.level11 {
+            shot.plugin_node.level11 = None;
+        }
+
+        self.update_trims(&mut shot.plugin_node.dv_dynamic_data);
+
+        // Level 5 can not exist in per-frame metadata anyway,
+        // but it's not our responsibility to validate it here.
+        // TODO: test case
+        if let Some(ref mut frames) = shot.frames {
+            frames.iter_mut().for_each(|frame| {
+                let plugin_node = &mut frame.plugin_node;
+                let dv_dynamic_data = &mut plugin_node.dv_dynamic_data;
+
+                let frame_level5 = dv_dynamic_data.level5.clone();
+                if frame_level5 == self.level5 || frame_level5 == shot_level5 {
+                    dv_dynamic_data.level5 = None;
+                }
+
+                plugin_node.level11 = None;
+
+                self.update_trims(&mut plugin_node.dv_dynamic_data);
+            })
+        }
+
+        shot
+    }
+
+    /// Remove trims of dropped target displays, and add identity trims of added ones.
+    fn update_trims(&self, data: &mut DVDynamicData) {
+        let options = &self.options;
+
+        if let Some(level2) = &mut data.level2 {
+            level2.retain(|level2| options.is_target_kept(level2.tid));
+        }
+
+        if let Some(level8) = &mut data.level8 {
+            level8.retain(|level8| options.is_target_kept(level8.tid()));
+        }
+
+        for &tid in &options.add_targets {
+            let has_trim = data.level2.iter().flatten().any(|level2| level2.tid == tid)
+                || data
+                    .level8
+                    .iter()
+                    .flatten()
+                    .any(|level8| level8.tid() == tid);
+
+            if !has_trim {
+                data.level2
+                    .get_or_insert_with(Vec::new)
+                    .push(Level2::with_tid(tid));
+            }
+        }
+
+        if data.level2.as_ref().is_some_and(Vec::is_empty) {
+            data.level2 = None;
+        }
+
+        if data.level8.as_ref().is_some_and(Vec::is_empty) {
+            data.level8 = None;
+        }
+    }
+
+    fn get_common<K, V>(map: &HashMap<Option<K>, V>) -> Option<K>
     where
         K: Clone,
         V: Copy + Ord,
     {
-        map.into_iter()
+        map.iter()
             .filter(|(value, _)| value.is_some())
             .max_by_key(|&(_, count)| count)
             .and_then(|(value, _)| value.clone())
     }
+}
 
-    // https://gist.github.com/lwilli/14fb3178bd9adac3a64edfbc11f42e0d/forks
-    fn prettify_xml(xml: String) -> String {
-        let mut buf = Vec::new();
+#[cfg(feature = "cli")]
+impl From<UniqueIdType> for IdMode {
+    fn from(unique_id: UniqueIdType) -> Self {
+        match unique_id {
+            UniqueIdType::Random => IdMode::Random,
+            UniqueIdType::Content => IdMode::Content,
+            UniqueIdType::Timecode => IdMode::Timecode,
+        }
+    }
+}
 
-        let mut reader = Reader::from_str(&xml);
-        reader.config_mut().trim_text(true);
+#[cfg(feature = "cli")]
+impl From<EdlModeType> for EdlMode {
+    fn from(edl_mode: EdlModeType) -> Self {
+        match edl_mode {
+            EdlModeType::Replace => EdlMode::Replace,
+            EdlModeType::Merge => EdlMode::Merge,
+        }
+    }
+}
 
-        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+#[cfg(feature = "cli")]
+impl From<ReelLayoutType> for ReelLayout {
+    fn from(reel_layout: ReelLayoutType) -> Self {
+        match reel_layout {
+            ReelLayoutType::Conformed => ReelLayout::Conformed,
+            ReelLayoutType::Outputs => ReelLayout::Outputs,
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<Eotf> for EncodingEnum {
+    fn from(eotf: Eotf) -> Self {
+        match eotf {
+            Eotf::Pq => EncodingEnum::Pq,
+            Eotf::Linear => EncodingEnum::Linear,
+            Eotf::GammaBt1886 => EncodingEnum::GammaBT1886,
+            Eotf::GammaDci => EncodingEnum::GammaDCI,
+            Eotf::Gamma22 => EncodingEnum::Gamma22,
+            Eotf::Gamma24 => EncodingEnum::Gamma24,
+            Eotf::Hlg => EncodingEnum::Hlg,
+        }
+    }
+}
 
-        loop {
-            let ev = reader.read_event_into(&mut buf);
+#[cfg(feature = "cli")]
+impl From<ColorSpaceType> for ColorSpaceEnum {
+    fn from(color_space: ColorSpaceType) -> Self {
+        match color_space {
+            ColorSpaceType::Rgb => ColorSpaceEnum::Rgb,
+            ColorSpaceType::Xyz => ColorSpaceEnum::Xyz,
+            ColorSpaceType::YCbCrBt709 => ColorSpaceEnum::YCbCrBT709,
+            ColorSpaceType::YCbCrBt2020 => ColorSpaceEnum::YCbCrBT2020,
+            ColorSpaceType::YCbCrNative => ColorSpaceEnum::YCbCrNative,
+        }
+    }
+}
 
-            match ev {
-                Ok(Event::Eof) => break,
-                Ok(event) => writer.write_event(event),
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-            }
-            .expect("Failed to parse XML");
+#[cfg(feature = "cli")]
+impl From<ChromaFormatType> for ChromaFormatEnum {
+    fn from(chroma_format: ChromaFormatType) -> Self {
+        match chroma_format {
+            ChromaFormatType::Yuv444 => ChromaFormatEnum::Yuv444,
+            ChromaFormatType::Yuv422 => ChromaFormatEnum::Yuv422,
+            ChromaFormatType::Yuv420 => ChromaFormatEnum::Yuv420,
+        }
+    }
+}
 
-            buf.clear();
+#[cfg(feature = "cli")]
+impl From<SignalRangeType> for SignalRangeEnum {
+    fn from(signal_range: SignalRangeType) -> Self {
+        match signal_range {
+            SignalRangeType::Computer => SignalRangeEnum::Computer,
+            SignalRangeType::Video => SignalRangeEnum::Video,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dolby_vision::rpu::dovi_rpu::DoviRpu;
+    use dolby_vision::rpu::extension_metadata::blocks::{
+        ExtMetadataBlock, ExtMetadataBlockLevel11, ExtMetadataBlockLevel2,
+    };
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+
+    use super::{ConvertOptions, Converter, Reel, ReelLayout};
+    use crate::display::{Characteristics, Primaries};
+    use crate::{MDFDocument, XMLVersion};
+
+    fn generate_rpus() -> Vec<DoviRpu> {
+        let config = GenerateConfig {
+            length: 5,
+            shots: vec![
+                VideoShot {
+                    duration: 2,
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 3,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        config.generate_rpu_list().unwrap()
+    }
+
+    #[test]
+    fn test_convert_rpus() {
+        let options = ConvertOptions {
+            skip: 1,
+            ..Default::default()
+        };
+
+        let MDFDocument::CMV40(md) = Converter::convert_rpus(generate_rpus(), options).unwrap()
+        else {
+            panic!("CM v4.0 RPU should be converted to CM v4.0 XML");
+        };
+
+        let shots = md.outputs.outputs[0].video.tracks[0]
+            .shots
+            .as_ref()
+            .unwrap();
+        assert_eq!(shots.len(), 2);
+        assert_eq!((shots[0].record.in_, shots[0].record.duration), (0, 1));
+        assert_eq!((shots[1].record.in_, shots[1].record.duration), (1, 3));
+
+        assert!(Converter::convert_rpus(Vec::new(), ConvertOptions::default()).is_err());
+    }
 
-        let result = std::str::from_utf8(&writer.into_inner())
-            .expect("Failed to convert a slice of bytes to a string slice")
-            .to_string();
+    #[test]
+    fn test_convert_reels() {
+        let reels = || {
+            let second = Reel {
+                record_in: Some(10),
+                ..Reel::new(generate_rpus())
+            };
 
-        result
+            vec![Reel::new(generate_rpus()), second]
+        };
+
+        let MDFDocument::CMV40(md) =
+            Converter::convert_reels(reels(), ConvertOptions::default()).unwrap()
+        else {
+            panic!("CM v4.0 RPU should be converted to CM v4.0 XML");
+        };
+
+        assert_eq!(md.outputs.outputs.len(), 1);
+        let record_ins = md.outputs.outputs[0].video.tracks[0]
+            .shots
+            .iter()
+            .flatten()
+            .map(|shot| shot.record.in_)
+            .collect::<Vec<_>>();
+        assert_eq!(record_ins, [0, 2, 10, 12]);
+
+        let options = ConvertOptions {
+            reel_layout: ReelLayout::Outputs,
+            ..Default::default()
+        };
+
+        let MDFDocument::CMV40(md) = Converter::convert_reels(reels(), options).unwrap() else {
+            panic!("CM v4.0 RPU should be converted to CM v4.0 XML");
+        };
+
+        assert_eq!(md.outputs.outputs.len(), 2);
+        assert_eq!(md.outputs.outputs[1].composition_name, "Reel 2");
+        let shots = md.outputs.outputs[1].video.tracks[0]
+            .shots
+            .as_ref()
+            .unwrap();
+        assert_eq!(shots[0].record.in_, 10);
+
+        let mut overlapped = reels();
+        overlapped[1].record_in = Some(4);
+        assert!(Converter::convert_reels(overlapped, ConvertOptions::default()).is_err());
+
+        for (skip, count) in [(1, None), (0, Some(4))] {
+            let options = ConvertOptions {
+                skip,
+                count,
+                ..Default::default()
+            };
+            assert!(Converter::convert_reels(reels(), options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_targets() {
+        let config = GenerateConfig {
+            length: 2,
+            shots: vec![VideoShot {
+                duration: 2,
+                metadata_blocks: [100, 600]
+                    .map(|nits| ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(nits)))
+                    .to_vec(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let options = ConvertOptions {
+            drop_targets: vec![27],
+            add_targets: vec![48],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap();
+        let MDFDocument::CMV40(md) = Converter::convert_rpus(rpus, options).unwrap() else {
+            panic!("CM v4.0 RPU should be converted to CM v4.0 XML");
+        };
+
+        let track = &md.outputs.outputs[0].video.tracks[0];
+        let targets = track.plugin_node.dv_global_data.target_displays.iter();
+        let ids = targets.flatten().map(|d| d.id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 48]);
+
+        let level2 = track.shots.as_ref().unwrap()[0]
+            .plugin_node
+            .dv_dynamic_data
+            .level2
+            .as_ref()
+            .unwrap();
+        assert_eq!(level2.iter().map(|l| l.tid).collect::<Vec<_>>(), [1, 48]);
+        assert!(level2[1].trim.into_inner().into_iter().all(|v| v == 0.0));
+
+        let options = ConvertOptions {
+            keep_targets: Some(vec![1]),
+            add_targets: vec![48],
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_level11() {
+        let shot = |duration, content_type| VideoShot {
+            duration,
+            metadata_blocks: vec![ExtMetadataBlock::Level11(ExtMetadataBlockLevel11 {
+                content_type,
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+
+        let config = GenerateConfig {
+            length: 5,
+            shots: vec![shot(2, 1), shot(1, 3), shot(2, 1)],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap();
+        let MDFDocument::CMV40(md) =
+            Converter::convert_rpus(rpus, ConvertOptions::default()).unwrap()
+        else {
+            panic!("CM v4.0 RPU should be converted to CM v4.0 XML");
+        };
+
+        let track = &md.outputs.outputs[0].video.tracks[0];
+        let level11 = track.plugin_node.level11.as_ref().unwrap();
+        assert_eq!(level11.content_type, 1);
+        assert_eq!(level11.extension_properties, None);
+
+        let content_types = track
+            .shots
+            .iter()
+            .flatten()
+            .map(|shot| shot.plugin_node.level11.as_ref().map(|l| l.content_type))
+            .collect::<Vec<_>>();
+        assert_eq!(content_types, [None, Some(3), None]);
+        assert_eq!(md.version, XMLVersion::V510.into());
+    }
+
+    #[test]
+    fn test_mastering_display() {
+        let options = ConvertOptions {
+            mastering_display: Characteristics::get_source(21),
+            ..Default::default()
+        };
+
+        let MDFDocument::CMV40(md) = Converter::convert_rpus(generate_rpus(), options).unwrap()
+        else {
+            panic!("CM v4.0 RPU should be converted to CM v4.0 XML");
+        };
+
+        let mastering_display = &md.outputs.outputs[0].video.tracks[0]
+            .plugin_node
+            .dv_global_data
+            .mastering_display;
+        assert_eq!(mastering_display.id, 21);
+        assert_eq!(mastering_display.peak_brightness, 1000);
+
+        let primaries = Primaries::get_index_primary(2, false).unwrap();
+        let source = Characteristics::custom_source(primaries, 1000, Some(0.0001));
+        assert_eq!(source.id, 21);
+
+        // Default minimum brightness
+        let source = Characteristics::custom_source(primaries, 1000, None);
+        assert_eq!(source.id, 21);
+
+        // Same code value
+        let source = Characteristics::custom_source(primaries, 1000, Some(0.000_100_1));
+        assert_eq!(source.id, 21);
+
+        let source = Characteristics::custom_source(primaries, 1000, Some(0.005));
+        assert_eq!(source.id, 3079);
+        assert_eq!(source.minimum_brightness, 0.005);
+
+        let source = Characteristics::custom_source(primaries, 1200, Some(0.0001));
+        assert_eq!(source.id, 3160);
+        assert_eq!(source.primary_index, 2);
+
+        let options = ConvertOptions {
+            mastering_display: Some(Characteristics::custom_source(
+                primaries,
+                1000,
+                Some(1000.0),
+            )),
+            ..Default::default()
+        };
+        assert!(Converter::convert_rpus(generate_rpus(), options).is_err());
     }
 }
@@ -0,0 +1,755 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::rpu::vdr_dm_data::VdrDmData;
+use itertools::Itertools;
+
+use crate::cmv40::{DVDynamicData, DolbyLabsMDF, Shot, Track};
+use crate::commands::csv::{ExportCsvArgs, ImportCsvArgs};
+use crate::display::PREDEFINED_TARGET_DISPLAYS;
+use crate::functions::field::{Block, Field};
+use crate::functions::input::Input;
+use crate::functions::{Generator, Migrator};
+use crate::{IntoCMV40, Level2, Level5, MDFDocument};
+
+const HEADER: [&str; 3] = ["type", "record_in", "duration"];
+
+/// Metadata of shots in the first track as CSV, one row per shot and optionally per frame.
+/// Values are written in full precision, so that unchanged cells are detected on import.
+#[derive(Debug)]
+pub struct CsvConverter {
+    mdf: DolbyLabsMDF,
+    is_cmv29: bool,
+    // Original XML is kept for other metadata of CM v2.9 XML
+    source: Input,
+    canvas: (usize, usize),
+    // Level 5 of output, for shots without it
+    level5: Level5,
+}
+
+#[derive(Debug)]
+struct Edit {
+    line: usize,
+    target: Target,
+    record_in: usize,
+    field: Field,
+    old: Option<f32>,
+    new: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Shot(usize),
+    // Index of shot, index of frame in shot
+    Frame(usize, usize),
+}
+
+impl CsvConverter {
+    pub fn export(args: ExportCsvArgs) -> Result<()> {
+        let input = match args.input {
+            Some(input) => input,
+            None => bail!("No input file provided."),
+        };
+
+        let converter = Self::open(&input, Input::canvas(&args.size)?)?;
+        let shots = converter.shots()?;
+
+        let data = shots.iter().flat_map(|shot| {
+            let frames = shot.frames.iter().flatten().filter(|_| args.per_frame);
+
+            std::iter::once(&shot.plugin_node.dv_dynamic_data)
+                .chain(frames.map(|frame| &frame.plugin_node.dv_dynamic_data))
+        });
+        let fields = Field::columns(data);
+
+        let output = if let Some(output) = args.output {
+            output
+        } else {
+            println!("No output file provided, writing to metadata.csv at current path...");
+            "./metadata.csv".into()
+        };
+
+        let mut writer = BufWriter::new(File::create(output)?);
+        writeln!(writer, "{},{}", HEADER.join(","), fields.iter().join(","))?;
+
+        let mut rows = 0;
+
+        for shot in shots {
+            let shot_data = converter.with_level5(&shot.plugin_node.dv_dynamic_data, None);
+            let record = &shot.record;
+
+            Self::write_row(
+                &mut writer,
+                [
+                    "shot",
+                    &record.in_.to_string(),
+                    &record.duration.to_string(),
+                ],
+                &fields,
+                &shot_data,
+            )?;
+            rows += 1;
+
+            if !args.per_frame {
+                continue;
+            }
+
+            for frame in shot.frames.iter().flatten() {
+                let data = converter.with_level5(
+                    &frame.plugin_node.dv_dynamic_data,
+                    shot_data.level5.as_ref(),
+                );
+
+                Self::write_row(
+                    &mut writer,
+                    ["frame", &(record.in_ + frame.edit_offset).to_string(), "1"],
+                    &fields,
+                    &data,
+                )?;
+                rows += 1;
+            }
+        }
+
+        writer.flush()?;
+
+        println!("Done. {rows} row(s) written.");
+
+        Ok(())
+    }
+
+    pub fn import(args: ImportCsvArgs) -> Result<()> {
+        let mut converter = Self::open(&args.input, Input::canvas(&args.size)?)?;
+
+        println!("Parsing CSV file...");
+
+        let edits = converter.edits(&fs::read_to_string(&args.csv)?)?;
+
+        if edits.is_empty() {
+            println!("No cell changed.");
+        } else {
+            println!("Changed cells:");
+
+            for edit in &edits {
+                let kind = match edit.target {
+                    Target::Shot(_) => "shot",
+                    Target::Frame(..) => "frame",
+                };
+                let old = edit.old.map_or("none".to_string(), |old| old.to_string());
+
+                println!(
+                    "  line {} ({kind} {}) {}: {old} -> {}",
+                    edit.line, edit.record_in, edit.field, edit.new
+                );
+            }
+
+            println!("{} cell(s) changed.", edits.len());
+        }
+
+        converter.apply(&edits)?;
+        converter.write(args.output)
+    }
+
+    fn open(input: &Path, canvas: (usize, usize)) -> Result<Self> {
+        let source = Input::open(input)?;
+        let document = source.to_document(canvas)?;
+
+        let is_cmv29 = matches!(document, MDFDocument::CMV29(_));
+        let mdf = document.into_cmv40();
+
+        let Some(output) = mdf.outputs.outputs.first() else {
+            bail!("No output in XML.");
+        };
+        let level5 = output.level5();
+
+        let tracks = mdf
+            .outputs
+            .outputs
+            .iter()
+            .map(|output| output.video.tracks.len())
+            .sum::<usize>();
+        if tracks > 1 {
+            println!("Only the first track is used, {tracks} tracks found.");
+        }
+
+        Ok(Self {
+            mdf,
+            is_cmv29,
+            source,
+            canvas,
+            level5,
+        })
+    }
+
+    fn track_mut(mdf: &mut DolbyLabsMDF) -> Result<&mut Track> {
+        mdf.outputs
+            .outputs
+            .first_mut()
+            .and_then(|output| output.video.tracks.first_mut())
+            .context("No track in output.")
+    }
+
+    fn shots(&self) -> Result<&[Shot]> {
+        self.mdf
+            .outputs
+            .outputs
+            .first()
+            .and_then(|output| output.video.tracks.first())
+            .map(|track| track.shots.as_deref().unwrap_or_default())
+            .context("No track in output.")
+    }
+
+    /// Level 5 is inherited from shot or output when not set.
+    fn with_level5(&self, data: &DVDynamicData, level5: Option<&Level5>) -> DVDynamicData {
+        let mut data = data.clone();
+
+        if data.level5.is_none() {
+            data.level5 = Some(level5.unwrap_or(&self.level5).clone());
+        }
+
+        data
+    }
+
+    fn write_row<W: Write>(
+        writer: &mut W,
+        head: [&str; 3],
+        fields: &[Field],
+        data: &DVDynamicData,
+    ) -> Result<()> {
+        let cells = fields
+            .iter()
+            .map(|field| field.get(data).map(|v| v.to_string()).unwrap_or_default());
+
+        writeln!(writer, "{},{}", head.join(","), cells.format(","))?;
+
+        Ok(())
+    }
+
+    /// Changed cells compared with the input, all invalid cells are reported at once.
+    fn edits(&self, content: &str) -> Result<Vec<Edit>> {
+        let shots = self.shots()?;
+        let shot_map = shots
+            .iter()
+            .enumerate()
+            .map(|(i, shot)| (shot.record.in_, i))
+            .collect::<BTreeMap<_, _>>();
+
+        // Spreadsheets may add BOM and empty lines
+        let mut lines = content
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let (_, header) = lines.next().context("Empty CSV file.")?;
+        let header = split_record(header);
+
+        ensure!(
+            header.len() >= 3 && header.iter().zip(HEADER).all(|(a, b)| a.trim() == b),
+            "The first columns of CSV must be {}.",
+            HEADER.join(", ")
+        );
+
+        let fields = header[3..]
+            .iter()
+            .map(|name| name.trim().parse::<Field>())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut edits = Vec::new();
+        let mut errors = Vec::new();
+
+        for (line, record) in lines {
+            let cells = split_record(record);
+            ensure!(
+                cells.len() <= header.len(),
+                "Line {line}: more cells than columns."
+            );
+
+            let number = |i: usize| {
+                let cell = cells.get(i).map_or("", |cell| cell.trim());
+                cell.parse::<usize>()
+                    .with_context(|| format!("Line {line}: invalid {} '{cell}'.", HEADER[i]))
+            };
+
+            let record_in = number(1)?;
+            let duration = number(2)?;
+
+            let (target, data) = match cells[0].trim() {
+                "shot" => {
+                    let &index = shot_map
+                        .get(&record_in)
+                        .with_context(|| format!("Line {line}: no shot at frame {record_in}."))?;
+                    let shot = &shots[index];
+
+                    ensure!(
+                        duration == shot.record.duration,
+                        "Line {line}: duration of shot at frame {record_in} is {}, not {duration}.",
+                        shot.record.duration
+                    );
+
+                    (
+                        Target::Shot(index),
+                        self.with_level5(&shot.plugin_node.dv_dynamic_data, None),
+                    )
+                }
+                "frame" => {
+                    ensure!(duration == 1, "Line {line}: duration of frame must be 1.");
+
+                    let (index, shot, frame_index) = shot_map
+                        .range(..=record_in)
+                        .next_back()
+                        .map(|(_, &index)| (index, &shots[index]))
+                        .and_then(|(index, shot)| {
+                            let offset = record_in - shot.record.in_;
+
+                            shot.frames
+                                .iter()
+                                .flatten()
+                                .position(|frame| frame.edit_offset == offset)
+                                .map(|frame_index| (index, shot, frame_index))
+                        })
+                        .with_context(|| {
+                            format!("Line {line}: no per-frame metadata at frame {record_in}.")
+                        })?;
+
+                    let shot_data = &shot.plugin_node.dv_dynamic_data;
+                    let frame_data = &shot.frames.as_ref().unwrap()[frame_index]
+                        .plugin_node
+                        .dv_dynamic_data;
+
+                    (
+                        Target::Frame(index, frame_index),
+                        self.with_level5(frame_data, shot_data.level5.as_ref()),
+                    )
+                }
+                kind => bail!("Line {line}: unknown row type '{kind}', expected shot or frame."),
+            };
+
+            for (&field, cell) in fields.iter().zip(cells.iter().skip(3)) {
+                let cell = cell.trim();
+
+                // Empty cell is unchanged
+                if cell.is_empty() {
+                    continue;
+                }
+
+                let Ok(new) = cell.parse::<f32>() else {
+                    errors.push(format!("line {line}, {field}: invalid number '{cell}'"));
+                    continue;
+                };
+
+                if !field.is_valid(new) {
+                    errors.push(format!(
+                        "line {line}, {field}: {new} is out of range, {}",
+                        field.range()
+                    ));
+                    continue;
+                }
+
+                let old = field.get(&data);
+
+                if old == Some(new) {
+                    continue;
+                }
+
+                if self.is_cmv29 && field.is_cmv40_only() {
+                    errors.push(format!(
+                        "line {line}, {field}: not supported by CM v2.9 metadata"
+                    ));
+                    continue;
+                }
+
+                edits.push(Edit {
+                    line,
+                    target,
+                    record_in,
+                    field,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("Invalid cells in CSV:\n  {}", errors.join("\n  "));
+        }
+
+        Ok(edits)
+    }
+
+    /// A changed shot cell is also applied to per-frame metadata of the shot as a delta,
+    /// so that changes across frames are kept, then changed frame cells are applied.
+    fn apply(&mut self, edits: &[Edit]) -> Result<()> {
+        let track = Self::track_mut(&mut self.mdf)?;
+        let shots = track.shots.get_or_insert_with(Vec::new);
+
+        for edit in edits {
+            let Target::Shot(index) = edit.target else {
+                continue;
+            };
+
+            let shot = &mut shots[index];
+            let data = &mut shot.plugin_node.dv_dynamic_data;
+
+            if edit.field.block() == Block::Level5 {
+                data.level5.get_or_insert_with(|| self.level5.clone());
+            }
+
+            edit.field.set(data, edit.new);
+
+            // Frames can't follow a value which is not in the shot
+            let Some(old) = edit.old else {
+                continue;
+            };
+
+            for frame in shot.frames.iter_mut().flatten() {
+                let data = &mut frame.plugin_node.dv_dynamic_data;
+
+                let new = match edit.field.get(data) {
+                    Some(value) if value == old => edit.new,
+                    Some(value) => edit.field.clamp(value + edit.new - old),
+                    None => continue,
+                };
+
+                edit.field.set(data, new);
+            }
+        }
+
+        for edit in edits {
+            let Target::Frame(index, frame_index) = edit.target else {
+                continue;
+            };
+
+            let shot = &mut shots[index];
+            let level5 = shot.plugin_node.dv_dynamic_data.level5.clone();
+
+            let Some(frame) = shot.frames.as_mut().and_then(|f| f.get_mut(frame_index)) else {
+                continue;
+            };
+            let data = &mut frame.plugin_node.dv_dynamic_data;
+
+            if edit.field.block() == Block::Level5 && data.level5.is_none() {
+                data.level5 = Some(level5.unwrap_or_else(|| self.level5.clone()));
+            }
+
+            edit.field.set(data, edit.new);
+        }
+
+        if let Input::Rpu(rpus) = &mut self.source {
+            Self::update_rpus(rpus, shots, edits, self.canvas, &self.level5)?;
+        }
+
+        Ok(())
+    }
+
+    /// Only the changed blocks are replaced in RPUs of each changed shot.
+    fn update_rpus(
+        rpus: &mut [DoviRpu],
+        shots: &[Shot],
+        edits: &[Edit],
+        canvas: (usize, usize),
+        level5: &Level5,
+    ) -> Result<()> {
+        let mut shot_blocks: BTreeMap<usize, BTreeSet<Block>> = BTreeMap::new();
+
+        for edit in edits {
+            let (Target::Shot(index) | Target::Frame(index, _)) = edit.target;
+            shot_blocks
+                .entry(index)
+                .or_default()
+                .insert(edit.field.block());
+        }
+
+        for (index, blocks) in shot_blocks {
+            let shot = &shots[index];
+            let shot_data = &shot.plugin_node.dv_dynamic_data;
+            let shot_level5 = shot_data.level5.as_ref().unwrap_or(level5);
+
+            let frames = shot
+                .frames
+                .iter()
+                .flatten()
+                .map(|frame| (frame.edit_offset, &frame.plugin_node.dv_dynamic_data))
+                .collect::<HashMap<_, _>>();
+
+            for offset in 0..shot.record.duration {
+                let frame_index = shot.record.in_ + offset;
+
+                let Some(rpu) = rpus.get_mut(frame_index) else {
+                    continue;
+                };
+                let Some(vdr) = rpu.vdr_dm_data.as_mut() else {
+                    continue;
+                };
+
+                // CRC is computed again when written
+                rpu.modified = true;
+
+                let data = frames.get(&offset).copied().unwrap_or(shot_data);
+                let level5 = data.level5.as_ref().unwrap_or(shot_level5);
+
+                for &block in &blocks {
+                    Self::replace_block(vdr, data, level5, block, canvas)
+                        .with_context(|| format!("Failed to update RPU at frame {frame_index}."))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn replace_block(
+        vdr: &mut VdrDmData,
+        data: &DVDynamicData,
+        level5: &Level5,
+        block: Block,
+        canvas: (usize, usize),
+    ) -> Result<()> {
+        let block = match block {
+            Block::Level1 => ExtMetadataBlock::Level1((&data.level1).into()),
+            Block::Level2(tid) => {
+                let Some(level2) = data.level2.iter().flatten().find(|l| l.tid == tid) else {
+                    return Ok(());
+                };
+
+                let target_max_pq = Self::target_max_pq(vdr, tid)?;
+                ExtMetadataBlock::Level2(level2.to_block_with_target_max_pq(target_max_pq))
+            }
+            Block::Level3 => match &data.level3 {
+                Some(level3) => ExtMetadataBlock::Level3(level3.into()),
+                None => return Ok(()),
+            },
+            Block::Level5 => ExtMetadataBlock::Level5(level5.to_block_with_canvas(canvas)),
+            Block::Level8(tid) => match data.level8.iter().flatten().find(|l| l.tid == tid) {
+                Some(level8) => ExtMetadataBlock::Level8(level8.into()),
+                None => return Ok(()),
+            },
+        };
+
+        vdr.replace_metadata_block(block)
+    }
+
+    // Existing blocks are replaced by target_max_pq, which is kept as is
+    fn target_max_pq(vdr: &VdrDmData, tid: usize) -> Result<u16> {
+        let primary = match vdr.get_block(9) {
+            Some(ExtMetadataBlock::Level9(block)) => Some(block.source_primary_index as usize),
+            _ => None,
+        };
+
+        let target_max_pq = vdr.level_blocks_iter(2).find_map(|block| match block {
+            ExtMetadataBlock::Level2(block)
+                if Level2::with_primary_index(block, primary).tid == tid =>
+            {
+                Some(block.target_max_pq)
+            }
+            _ => None,
+        });
+
+        if let Some(target_max_pq) = target_max_pq {
+            return Ok(target_max_pq);
+        }
+
+        match PREDEFINED_TARGET_DISPLAYS.iter().find(|d| d[0] == tid) {
+            Some(d) => Ok(Generator::rpu_pq_u12_from_nits(d[2] as f32)),
+            None => bail!("Unknown target display ID {tid} in Level2 metadata."),
+        }
+    }
+
+    fn write(self, output: Option<PathBuf>) -> Result<()> {
+        match self.source {
+            Input::Xml(xml) => {
+                let mut document = match MDFDocument::from_xml_str(&xml)? {
+                    MDFDocument::CMV29(mut mdf) => {
+                        Self::copy_shots(&mut mdf, self.mdf)?;
+                        MDFDocument::CMV29(mdf)
+                    }
+                    MDFDocument::CMV40(_) => MDFDocument::CMV40(self.mdf),
+                };
+
+                match &mut document {
+                    MDFDocument::CMV29(mdf) => Migrator::add_revision(&mut mdf.revision_history),
+                    MDFDocument::CMV40(mdf) => Migrator::add_revision(&mut mdf.revision_history),
+                }
+
+                let output = if let Some(output) = output {
+                    output
+                } else {
+                    println!("No output file provided, writing to metadata.xml at current path...");
+                    "./metadata.xml".into()
+                };
+
+                let mut writer = BufWriter::new(File::create(output)?);
+                write!(writer, "{}", document.to_xml_string()?)?;
+                writer.flush()?;
+            }
+            Input::Rpu(rpus) => {
+                let output = if let Some(output) = output {
+                    output
+                } else {
+                    println!("No output file provided, writing to RPU.bin at current path...");
+                    "./RPU.bin".into()
+                };
+
+                Generator::write_rpus(output, &rpus)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shot metadata of the first track is copied back,
+    /// other metadata in CM v2.9 XML can't be converted without loss.
+    fn copy_shots(mdf: &mut crate::cmv29::DolbyLabsMDF, edited: DolbyLabsMDF) -> Result<()> {
+        let track = mdf
+            .outputs
+            .iter_mut()
+            .flat_map(|outputs| outputs.outputs.iter_mut().flatten())
+            .flat_map(|output| output.video.tracks.iter_mut())
+            .next()
+            .context("No track in output.")?;
+
+        let mut edited = edited;
+        let shots = Self::track_mut(&mut edited)?.shots.take();
+
+        for (shot, edited_shot) in track
+            .shots
+            .iter_mut()
+            .flatten()
+            .zip(shots.into_iter().flatten())
+        {
+            shot.plugin_node = edited_shot.plugin_node.dv_dynamic_data.into();
+
+            for (frame, edited_frame) in shot
+                .frames
+                .iter_mut()
+                .flatten()
+                .zip(edited_shot.frames.into_iter().flatten())
+            {
+                frame.plugin_node = edited_frame.plugin_node.dv_dynamic_data.into();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Cells of a CSV record, quotes are removed.
+fn split_record(record: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+
+    cells.push(cell);
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel1};
+    use dolby_vision::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
+
+    use super::{split_record, CsvConverter};
+    use crate::functions::field::Field;
+    use crate::functions::input::Input;
+    use crate::functions::{ConvertOptions, Converter};
+    use crate::IntoCMV40;
+
+    #[test]
+    fn test_apply_per_frame() {
+        let level1 =
+            |max_pq| ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::new(0, max_pq, 1024));
+
+        let config = GenerateConfig {
+            length: 3,
+            shots: vec![VideoShot {
+                duration: 3,
+                metadata_blocks: vec![level1(2048)],
+                frame_edits: vec![
+                    ShotFrameEdit {
+                        edit_offset: 1,
+                        metadata_blocks: vec![level1(2867)],
+                    },
+                    ShotFrameEdit {
+                        edit_offset: 2,
+                        metadata_blocks: vec![level1(2048)],
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap();
+        let mdf = Converter::convert_rpus(rpus.clone(), ConvertOptions::default())
+            .unwrap()
+            .into_cmv40();
+        let level5 = mdf.outputs.outputs[0].level5();
+
+        let mut converter = CsvConverter {
+            mdf,
+            is_cmv29: false,
+            source: Input::Rpu(rpus),
+            canvas: (3840, 2160),
+            level5,
+        };
+
+        let edits = converter
+            .edits("type,record_in,duration,l1_max\nshot,0,3,0.6")
+            .unwrap();
+        assert_eq!(edits.len(), 1);
+        let delta = 0.6 - edits[0].old.unwrap();
+
+        converter.apply(&edits).unwrap();
+
+        let field = Field::Level1(2);
+        let shot = &converter.shots().unwrap()[0];
+        assert_eq!(field.get(&shot.plugin_node.dv_dynamic_data), Some(0.6));
+
+        let frames = shot.frames.as_ref().unwrap();
+        let max = field.get(&frames[0].plugin_node.dv_dynamic_data).unwrap();
+        assert!((max - (2867.0 / 4095.0 + delta)).abs() < 1e-6);
+
+        let Input::Rpu(rpus) = &converter.source else {
+            unreachable!()
+        };
+        let max_pq = rpus
+            .iter()
+            .map(|rpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(1) {
+                Some(ExtMetadataBlock::Level1(block)) => block.max_pq,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(max_pq, [2457, 3276, 2457]);
+    }
+
+    #[test]
+    fn test_split_record() {
+        assert_eq!(
+            split_record("shot,0,24,,0.5"),
+            ["shot", "0", "24", "", "0.5"]
+        );
+        assert_eq!(
+            split_record("\"frame\",\"1,000\",\"a \"\"b\"\"\""),
+            ["frame", "1,000", "a \"b\""]
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use anyhow::{ensure, Result};
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::rpu::vdr_dm_data::VdrDmData;
+
+use crate::cmv40::DVDynamicData;
+#[cfg(feature = "cli")]
+use crate::commands::CutDetectionArgs;
+use crate::metadata::WithTid;
+use crate::{Level1, Level2, Level8, RPU_PQ_MAX, UHD_CANVAS};
+
+/// Thresholds of discontinuities in per-frame metadata to detect a cut.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CutThresholds {
+    /// Minimum jump of min, avg or max PQ in Level 1, in range 0 to 1.
+    pub level1: f32,
+    /// Minimum change of any trim in Level 2 or Level 8, trims are in range -1 to 1.
+    pub trim: f32,
+}
+
+impl Default for CutThresholds {
+    fn default() -> Self {
+        Self {
+            level1: 0.03,
+            trim: 0.05,
+        }
+    }
+}
+
+impl CutThresholds {
+    /// None when cut detection is not enabled.
+    #[cfg(feature = "cli")]
+    pub fn from_args(args: &CutDetectionArgs) -> Option<Self> {
+        args.detect_cuts.then_some(Self {
+            level1: args.cut_level1_threshold,
+            trim: args.cut_trim_threshold,
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.level1 > 0.0 && self.level1 <= 1.0,
+            "Invalid Level 1 threshold of cut detection."
+        );
+        ensure!(
+            self.trim > 0.0 && self.trim <= 2.0,
+            "Invalid trim threshold of cut detection."
+        );
+
+        Ok(())
+    }
+}
+
+/// Detects cuts for RPU with per-frame metadata, where every frame may be marked as a new scene.
+/// A scene refresh is only kept as a cut when metadata jumps from the previous frame,
+/// so smoothly changing frames stay in the same shot.
+#[derive(Debug, Clone)]
+pub struct CutDetector {
+    thresholds: CutThresholds,
+    previous: Option<DVDynamicData>,
+}
+
+impl CutDetector {
+    pub fn new(thresholds: CutThresholds) -> Self {
+        Self {
+            thresholds,
+            previous: None,
+        }
+    }
+
+    /// Whether the frame starts a new shot, frames must be passed in order.
+    pub fn is_cut(&mut self, vdr: &VdrDmData) -> bool {
+        let current = DVDynamicData::with_canvas(vdr, UHD_CANVAS);
+
+        let is_cut = vdr.scene_refresh_flag == 1
+            && self
+                .previous
+                .as_ref()
+                .map_or(true, |previous| self.is_discontinuous(previous, &current));
+
+        self.previous = Some(current);
+
+        is_cut
+    }
+
+    fn is_discontinuous(&self, previous: &DVDynamicData, current: &DVDynamicData) -> bool {
+        let level1_jump = previous
+            .level1
+            .image_character
+            .into_inner()
+            .into_iter()
+            .zip(current.level1.image_character.into_inner())
+            .any(|(a, b)| (a - b).abs() >= self.thresholds.level1);
+
+        level1_jump
+            || self.trims_changed(&previous.level2, &current.level2, Self::level2_trims)
+            || self.trims_changed(&previous.level8, &current.level8, Self::level8_trims)
+    }
+
+    /// Trims of each target display are compared, adding or removing a target is a change.
+    fn trims_changed<T, F>(
+        &self,
+        previous: &Option<Vec<T>>,
+        current: &Option<Vec<T>>,
+        trims: F,
+    ) -> bool
+    where
+        T: WithTid,
+        F: Fn(&T) -> Vec<f32>,
+    {
+        let previous = previous.as_deref().unwrap_or_default();
+        let current = current.as_deref().unwrap_or_default();
+
+        previous.len() != current.len()
+            || previous
+                .iter()
+                .any(|a| match current.iter().find(|b| b.tid() == a.tid()) {
+                    Some(b) => trims(a)
+                        .into_iter()
+                        .zip(trims(b))
+                        .any(|(a, b)| (a - b).abs() >= self.thresholds.trim),
+                    None => true,
+                })
+    }
+
+    fn level2_trims(level2: &Level2) -> Vec<f32> {
+        level2.trim.into_inner().into_iter().collect()
+    }
+
+    fn level8_trims(level8: &Level8) -> Vec<f32> {
+        level8
+            .l8_trim
+            .into_inner()
+            .into_iter()
+            .chain([level8.mid_contrast_bias, level8.highlight_clipping])
+            .chain(level8.sat_vector_field.into_inner())
+            .chain(level8.hue_vector_field.into_inner())
+            .collect()
+    }
+}
+
+/// A dissolve or fade across a cut, where the transition starts before the cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dissolve {
+    /// First frame of the transition.
+    pub start: usize,
+    /// First frame of the incoming shot.
+    pub cut: usize,
+    pub duration: usize,
+}
+
+/// Detects dissolves as monotonic ramps of average PQ in Level 1 across a cut.
+/// Only frames in the current ramp are kept, so it can be used with frames in a stream.
+#[derive(Debug, Default)]
+pub struct DissolveDetector {
+    previous: Option<(usize, f32)>,
+    // Direction of the current ramp, 0 when there is no ramp
+    direction: i8,
+    ramp_start: usize,
+    cuts_in_ramp: Vec<usize>,
+    dissolves: Vec<Dissolve>,
+}
+
+impl DissolveDetector {
+    /// Minimum frames of a ramp to be a dissolve.
+    pub const MIN_DURATION: usize = 4;
+    // Steps smaller than half of a 12-bit code value are flat
+    const MIN_STEP: f32 = 0.5 / RPU_PQ_MAX;
+
+    /// Add a frame in order, with whether it starts a new shot.
+    pub fn update(&mut self, index: usize, vdr: &VdrDmData, is_cut: bool) {
+        let avg = match vdr.get_block(1) {
+            Some(ExtMetadataBlock::Level1(block)) => Level1::from(block),
+            _ => Level1::default(),
+        }
+        .image_character
+        .into_inner()
+        .into_iter()
+        .nth(1)
+        .unwrap_or_default();
+
+        let direction = match self.previous {
+            // A missing frame breaks the ramp
+            Some((previous_index, previous)) if previous_index + 1 == index => {
+                if avg - previous > Self::MIN_STEP {
+                    1
+                } else if previous - avg > Self::MIN_STEP {
+                    -1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        };
+
+        if direction == 0 || direction != self.direction {
+            self.finish_ramp(index);
+            self.direction = direction;
+            self.ramp_start = index;
+        }
+
+        if is_cut && direction != 0 {
+            self.cuts_in_ramp.push(index);
+        }
+
+        self.previous = Some((index, avg));
+    }
+
+    /// Dissolves in order, `end` is the index after the last frame.
+    pub fn finish(mut self, end: usize) -> Vec<Dissolve> {
+        self.finish_ramp(end);
+
+        self.dissolves
+    }
+
+    fn finish_ramp(&mut self, end: usize) {
+        let cuts = std::mem::take(&mut self.cuts_in_ramp);
+
+        // A ramp with multiple cuts is ambiguous, the first frame of ramp is not across the cut
+        if let [cut] = cuts[..] {
+            let duration = end - self.ramp_start;
+
+            if cut > self.ramp_start && duration >= Self::MIN_DURATION {
+                self.dissolves.push(Dissolve {
+                    start: self.ramp_start,
+                    cut,
+                    duration,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use dolby_vision::rpu::dovi_rpu::DoviRpu;
+    use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel1};
+    use dolby_vision::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
+
+    use super::{CutDetector, CutThresholds, Dissolve, DissolveDetector};
+    use crate::functions::{ShotOptions, ShotReader};
+
+    fn level1(avg_pq: u16) -> Vec<ExtMetadataBlock> {
+        vec![ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::new(
+            0, 3000, avg_pq,
+        ))]
+    }
+
+    // Scene refresh flag is set in every frame, the first shot fades slowly.
+    fn generate_rpus() -> Vec<DoviRpu> {
+        let config = GenerateConfig {
+            long_play_mode: true,
+            length: 5,
+            shots: vec![
+                VideoShot {
+                    duration: 3,
+                    metadata_blocks: level1(1000),
+                    frame_edits: vec![
+                        ShotFrameEdit {
+                            edit_offset: 1,
+                            metadata_blocks: level1(1010),
+                        },
+                        ShotFrameEdit {
+                            edit_offset: 2,
+                            metadata_blocks: level1(1020),
+                        },
+                    ],
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 2,
+                    metadata_blocks: level1(2000),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        config.generate_rpu_list().unwrap()
+    }
+
+    #[test]
+    fn test_cut_detection() {
+        let mut detector = CutDetector::new(CutThresholds::default());
+        let cuts = generate_rpus()
+            .iter()
+            .enumerate()
+            .filter(|(_, rpu)| detector.is_cut(rpu.vdr_dm_data.as_ref().unwrap()))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(cuts, [0, 3]);
+
+        let options = ShotOptions {
+            cut_detection: Some(CutThresholds::default()),
+            ..Default::default()
+        };
+        let shots = ShotReader::new(generate_rpus().into_iter().map(Ok), options)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(shots.len(), 2);
+        assert_eq!(shots[0].record.duration, 3);
+        assert_eq!(shots[0].frames.as_ref().map(Vec::len), Some(2));
+
+        let shots = ShotReader::new(generate_rpus().into_iter().map(Ok), ShotOptions::default());
+        assert_eq!(shots.count(), 5);
+    }
+
+    #[test]
+    fn test_dissolve_detection() {
+        let edits = |values: &[(usize, u16)]| {
+            values
+                .iter()
+                .map(|&(edit_offset, avg_pq)| ShotFrameEdit {
+                    edit_offset,
+                    metadata_blocks: level1(avg_pq),
+                })
+                .collect()
+        };
+
+        // Average PQ: 1000 1000 1000 1100 1200 1300 | 1400 1500 1600 1600
+        let config = GenerateConfig {
+            length: 10,
+            shots: vec![
+                VideoShot {
+                    duration: 6,
+                    metadata_blocks: level1(1000),
+                    frame_edits: edits(&[(3, 1100), (4, 1200), (5, 1300)]),
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 4,
+                    metadata_blocks: level1(1600),
+                    frame_edits: edits(&[(0, 1400), (1, 1500)]),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut detector = DissolveDetector::default();
+        for (i, rpu) in config.generate_rpu_list().unwrap().iter().enumerate() {
+            let vdr = rpu.vdr_dm_data.as_ref().unwrap();
+            detector.update(i, vdr, i > 0 && vdr.scene_refresh_flag == 1);
+        }
+
+        assert_eq!(
+            detector.finish(10),
+            [Dissolve {
+                start: 3,
+                cut: 6,
+                duration: 6
+            }]
+        );
+
+        // Ramps shorter than the minimum duration
+        let mut detector = DissolveDetector::default();
+        for (i, rpu) in generate_rpus().iter().enumerate() {
+            detector.update(i, rpu.vdr_dm_data.as_ref().unwrap(), i == 3);
+        }
+        assert!(detector.finish(5).is_empty());
+    }
+}
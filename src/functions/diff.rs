@@ -0,0 +1,580 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{bail, ensure, Result};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::cmv40::{Characteristics, DVDynamicData, Track};
+use crate::commands::analyze::ReportFormat;
+use crate::commands::diff::DiffArgs;
+use crate::functions::field::Field;
+use crate::functions::input::Input;
+use crate::{IntoCMV40, Level11, Level254, Level5, Level6};
+
+/// Compares metadata of the first track in two inputs, aligned by frame from the first shot.
+#[derive(Debug)]
+pub struct Differ {
+    tolerance: f32,
+}
+
+#[derive(Debug)]
+struct Metadata {
+    track: Track,
+    // Level 5 of output, for shots without it
+    level5: Level5,
+}
+
+// Metadata of a frame, with the Level 5 inherited from shot or output
+#[derive(Debug, Clone, Copy)]
+struct FrameData<'a> {
+    data: &'a DVDynamicData,
+    level5: &'a Level5,
+}
+
+impl Differ {
+    pub fn diff(args: DiffArgs) -> Result<()> {
+        ensure!(
+            args.tolerance.is_finite() && args.tolerance >= 0.0,
+            "Invalid tolerance."
+        );
+
+        let canvas = Input::canvas(&args.size)?;
+
+        let metadata = Metadata::open(&args.input, canvas)?;
+        let other = Metadata::open(&args.other, canvas)?;
+
+        let differ = Differ {
+            tolerance: args.tolerance,
+        };
+        let report = differ.compare(&metadata, &other);
+
+        let report = match args.format {
+            ReportFormat::Text => report.to_string(),
+            ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        };
+
+        if let Some(output) = args.output {
+            let mut writer = BufWriter::new(File::create(output)?);
+            writeln!(writer, "{report}")?;
+            writer.flush()?;
+        } else {
+            println!("{report}");
+        }
+
+        Ok(())
+    }
+
+    fn compare(&self, metadata: &Metadata, other: &Metadata) -> Report {
+        let frames = metadata.frames();
+        let other_frames = other.frames();
+
+        let cuts = metadata.cuts();
+        let other_cuts = other.cuts();
+
+        let fields = Field::columns(
+            frames
+                .iter()
+                .chain(&other_frames)
+                .flatten()
+                .map(|frame| frame.data)
+                .dedup_by(|a, b| std::ptr::eq(*a, *b)),
+        );
+
+        let shots = metadata
+            .shot_ranges()
+            .filter_map(|(start, duration)| {
+                let end = (start + duration).min(frames.len()).min(other_frames.len());
+                let range = start.min(end)..end;
+
+                let changes = fields
+                    .iter()
+                    .filter_map(|field| {
+                        self.compare_field(
+                            *field,
+                            &frames[range.clone()],
+                            &other_frames[range.clone()],
+                            start,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                (!changes.is_empty()).then_some(ShotChanges {
+                    start,
+                    duration,
+                    changes,
+                })
+            })
+            .collect();
+
+        Report {
+            tolerance: self.tolerance,
+            frames: FrameCounts {
+                original: frames.iter().flatten().count(),
+                other: other_frames.iter().flatten().count(),
+            },
+            added_cuts: other_cuts.difference(&cuts).copied().collect(),
+            removed_cuts: cuts.difference(&other_cuts).copied().collect(),
+            shots,
+            global: Self::compare_global(&metadata.track, &other.track),
+        }
+    }
+
+    /// Values at the frame with the largest difference in a shot.
+    fn compare_field(
+        &self,
+        field: Field,
+        frames: &[Option<FrameData>],
+        other_frames: &[Option<FrameData>],
+        start: usize,
+    ) -> Option<FieldChange> {
+        let mut change: Option<FieldChange> = None;
+
+        for (i, (frame, other_frame)) in frames.iter().zip(other_frames).enumerate() {
+            // Frame in a gap between shots of one of the inputs
+            let (Some(frame), Some(other_frame)) = (frame, other_frame) else {
+                continue;
+            };
+
+            let value = frame.value(field);
+            let other_value = other_frame.value(field);
+
+            let difference = match (value, other_value) {
+                (Some(a), Some(b)) => (a - b).abs(),
+                (None, None) => continue,
+                // Level or target display is missing in one of the inputs
+                _ => f32::INFINITY,
+            };
+
+            if difference <= self.tolerance {
+                continue;
+            }
+
+            let change = change.get_or_insert_with(|| FieldChange {
+                field: field.to_string(),
+                frame: start + i,
+                original: value,
+                other: other_value,
+                max_difference: None,
+                frames: 0,
+            });
+
+            change.frames += 1;
+
+            if change.frames == 1 || difference > change.max_difference.unwrap_or(f32::INFINITY) {
+                change.frame = start + i;
+                change.original = value;
+                change.other = other_value;
+                change.max_difference = difference.is_finite().then_some(difference);
+            }
+        }
+
+        change
+    }
+
+    fn compare_global(track: &Track, other: &Track) -> Vec<GlobalChange> {
+        let mut changes = Vec::new();
+
+        let mut push = |name: String, original: Option<String>, other: Option<String>| {
+            if original != other {
+                changes.push(GlobalChange {
+                    name,
+                    original,
+                    other,
+                });
+            }
+        };
+
+        let global_data = &track.plugin_node.dv_global_data;
+        let other_global_data = &other.plugin_node.dv_global_data;
+
+        push(
+            "Mastering display".to_string(),
+            Some(describe_display(&global_data.mastering_display)),
+            Some(describe_display(&other_global_data.mastering_display)),
+        );
+
+        let targets = Self::targets(track);
+        let other_targets = Self::targets(other);
+
+        for id in targets.keys().chain(other_targets.keys()).unique().sorted() {
+            push(
+                format!("Target display {id}"),
+                targets.get(id).cloned(),
+                other_targets.get(id).cloned(),
+            );
+        }
+
+        push(
+            "Level6".to_string(),
+            track.level6.as_ref().map(describe_level6),
+            other.level6.as_ref().map(describe_level6),
+        );
+        push(
+            "Level11".to_string(),
+            track.plugin_node.level11.as_ref().map(describe_level11),
+            other.plugin_node.level11.as_ref().map(describe_level11),
+        );
+        push(
+            "Level254".to_string(),
+            track.plugin_node.level254.as_ref().map(describe_level254),
+            other.plugin_node.level254.as_ref().map(describe_level254),
+        );
+
+        changes
+    }
+
+    fn targets(track: &Track) -> BTreeMap<usize, String> {
+        track
+            .plugin_node
+            .dv_global_data
+            .target_displays
+            .iter()
+            .flatten()
+            .map(|d| (d.id, describe_display(d)))
+            .collect()
+    }
+}
+
+impl Metadata {
+    fn open(input: &Path, canvas: (usize, usize)) -> Result<Self> {
+        let mdf = Input::open(input)?.to_document(canvas)?.into_cmv40();
+
+        let Some(output) = mdf.outputs.outputs.into_iter().next() else {
+            bail!("No output in XML.");
+        };
+
+        let level5 = output.level5();
+
+        let Some(mut track) = output.video.tracks.into_iter().next() else {
+            bail!("No track in output.");
+        };
+
+        if let Some(shots) = &mut track.shots {
+            shots.sort_by_key(|shot| shot.record.in_);
+        }
+
+        Ok(Self { track, level5 })
+    }
+
+    fn first_frame(&self) -> usize {
+        self.track
+            .shots
+            .iter()
+            .flatten()
+            .next()
+            .map_or(0, |shot| shot.record.in_)
+    }
+
+    /// Start and duration of shots, from the first shot.
+    fn shot_ranges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let first_frame = self.first_frame();
+
+        self.track
+            .shots
+            .iter()
+            .flatten()
+            .map(move |shot| (shot.record.in_ - first_frame, shot.record.duration))
+    }
+
+    fn cuts(&self) -> BTreeSet<usize> {
+        self.shot_ranges().map(|(start, _)| start).collect()
+    }
+
+    /// Metadata of each frame from the first shot, None in gaps between shots.
+    fn frames(&self) -> Vec<Option<FrameData<'_>>> {
+        let mut frames = Vec::new();
+        let shots = self.track.shots.iter().flatten();
+
+        for (shot, (start, _)) in shots.zip(self.shot_ranges()) {
+            frames.resize(start, None);
+
+            let shot_data = &shot.plugin_node.dv_dynamic_data;
+            let shot_level5 = shot_data.level5.as_ref().unwrap_or(&self.level5);
+            let mut shot_frames = shot.frames.iter().flatten().peekable();
+
+            for offset in 0..shot.record.duration {
+                let data = match shot_frames.next_if(|frame| frame.edit_offset == offset) {
+                    Some(frame) => &frame.plugin_node.dv_dynamic_data,
+                    None => shot_data,
+                };
+
+                frames.push(Some(FrameData {
+                    data,
+                    level5: data.level5.as_ref().unwrap_or(shot_level5),
+                }));
+            }
+        }
+
+        frames
+    }
+}
+
+impl FrameData<'_> {
+    fn value(&self, field: Field) -> Option<f32> {
+        match field {
+            Field::Level5(i) => {
+                let (canvas, image) = self.level5.get_ar();
+                Some(if i == 0 { canvas } else { image })
+            }
+            _ => field.get(self.data),
+        }
+    }
+}
+
+fn describe_display(display: &Characteristics) -> String {
+    let primaries = display.primaries.with_white_point(display.white_point);
+    let primaries = match primaries.get_index() {
+        Some(index) => index.to_string(),
+        None => format!("{:?}", primaries.to_rpu_u16()),
+    };
+
+    format!(
+        "{} nits peak, {} nits minimum, primaries {primaries}",
+        display.peak_brightness, display.minimum_brightness
+    )
+}
+
+fn describe_level6(level6: &Level6) -> String {
+    format!("MaxCLL {}, MaxFALL {}", level6.max_cll, level6.max_fall)
+}
+
+fn describe_level11(level11: &Level11) -> String {
+    format!(
+        "content type {}, intended white point {}",
+        level11.content_type, level11.intended_white_point
+    )
+}
+
+fn describe_level254(level254: &Level254) -> String {
+    format!(
+        "CM version {}, DM mode {}, DM version {}",
+        level254.cm_version, level254.dm_mode, level254.dm_version
+    )
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    tolerance: f32,
+    frames: FrameCounts,
+    added_cuts: Vec<usize>,
+    removed_cuts: Vec<usize>,
+    // Shots of the original input with changed values
+    shots: Vec<ShotChanges>,
+    global: Vec<GlobalChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct FrameCounts {
+    original: usize,
+    other: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ShotChanges {
+    start: usize,
+    duration: usize,
+    changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct FieldChange {
+    field: String,
+    // Frame with the largest difference
+    frame: usize,
+    original: Option<f32>,
+    other: Option<f32>,
+    // None if the value is missing in one of the inputs
+    max_difference: Option<f32>,
+    // Number of frames with difference
+    frames: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct GlobalChange {
+    name: String,
+    original: Option<String>,
+    other: Option<String>,
+}
+
+impl Report {
+    fn is_empty(&self) -> bool {
+        self.frames.original == self.frames.other
+            && self.added_cuts.is_empty()
+            && self.removed_cuts.is_empty()
+            && self.shots.is_empty()
+            && self.global.is_empty()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No difference found, with tolerance {}.", self.tolerance);
+        }
+
+        let none = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+
+        writeln!(
+            f,
+            "Frames: {} -> {}",
+            self.frames.original, self.frames.other
+        )?;
+
+        for (name, cuts) in [("Added", &self.added_cuts), ("Removed", &self.removed_cuts)] {
+            if cuts.is_empty() {
+                writeln!(f, "{name} cuts: None")?;
+            } else {
+                writeln!(f, "{name} cuts at frame: {}", cuts.iter().join(", "))?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Shots with changes (tolerance {}): {}",
+            self.tolerance,
+            self.shots.len()
+        )?;
+
+        for shot in &self.shots {
+            writeln!(
+                f,
+                "  Shot at frame {} ({} frame(s)):",
+                shot.start, shot.duration
+            )?;
+
+            for change in &shot.changes {
+                write!(
+                    f,
+                    "    {}: {} -> {} at frame {}",
+                    change.field,
+                    none(change.original.map(|v| v.to_string())),
+                    none(change.other.map(|v| v.to_string())),
+                    change.frame
+                )?;
+
+                if let Some(max_difference) = change.max_difference {
+                    write!(f, ", max difference {max_difference}")?;
+                }
+
+                writeln!(f, " in {} frame(s)", change.frames)?;
+            }
+        }
+
+        write!(f, "Global metadata with changes: {}", self.global.len())?;
+
+        for change in &self.global {
+            write!(
+                f,
+                "\n  {}: {} -> {}",
+                change.name,
+                none(change.original.clone()),
+                none(change.other.clone())
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+
+    use super::{Differ, FrameData, Metadata};
+    use crate::cmv40::DVDynamicData;
+    use crate::functions::field::Field;
+    use crate::functions::{ConvertOptions, Converter};
+    use crate::{IntoCMV40, Level5};
+
+    fn generate_metadata(durations: &[usize]) -> Metadata {
+        let config = GenerateConfig {
+            length: durations.iter().sum(),
+            shots: durations
+                .iter()
+                .map(|&duration| VideoShot {
+                    duration,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap();
+        let mut mdf = Converter::convert_rpus(rpus, ConvertOptions::default())
+            .unwrap()
+            .into_cmv40();
+        let output = mdf.outputs.outputs.remove(0);
+
+        Metadata {
+            level5: output.level5(),
+            track: output.video.tracks.into_iter().next().unwrap(),
+        }
+    }
+
+    fn frames<'a>(data: &[&'a DVDynamicData], level5: &'a Level5) -> Vec<Option<FrameData<'a>>> {
+        data.iter()
+            .map(|&data| Some(FrameData { data, level5 }))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_field() {
+        let level5 = Level5::from(16.0 / 9.0);
+
+        let mut data = DVDynamicData::default();
+        Field::Level2(1, 1).set(&mut data, 0.1);
+        let mut close = data.clone();
+        Field::Level2(1, 1).set(&mut close, 0.1005);
+        let mut changed = data.clone();
+        Field::Level2(1, 1).set(&mut changed, 0.2);
+        let missing = DVDynamicData::default();
+
+        let differ = Differ { tolerance: 0.001 };
+        let field = Field::Level2(1, 1);
+
+        let a = frames(&[&data, &data, &data], &level5);
+        let b = frames(&[&close, &changed, &close], &level5);
+        let change = differ.compare_field(field, &a, &b, 10).unwrap();
+
+        assert_eq!(change.frames, 1);
+        assert_eq!(change.frame, 11);
+        assert_eq!(change.other, Some(0.2));
+        assert!((change.max_difference.unwrap() - 0.1).abs() < 1e-6);
+
+        // Missing value is reported over any difference
+        let b = frames(&[&close, &changed, &missing], &level5);
+        let change = differ.compare_field(field, &a, &b, 10).unwrap();
+        assert_eq!((change.frames, change.frame, change.other), (2, 12, None));
+        assert_eq!(change.max_difference, None);
+
+        assert!(differ.compare_field(Field::Level5(1), &a, &b, 10).is_none());
+    }
+
+    #[test]
+    fn test_compare_gap() {
+        // Shots at 0..2 and 4..7, with a gap of 2 frames
+        let mut metadata = generate_metadata(&[2, 3]);
+        metadata.track.shots.as_mut().unwrap()[1].record.in_ = 4;
+
+        let frames = metadata.frames();
+        assert_eq!(frames.len(), 7);
+        assert!(frames[2..4].iter().all(Option::is_none));
+
+        let other = generate_metadata(&[2, 8]);
+        let differ = Differ { tolerance: 0.0 };
+
+        let report = differ.compare(&metadata, &other);
+        assert_eq!((report.frames.original, report.frames.other), (5, 10));
+        assert_eq!(report.added_cuts, [2]);
+        assert_eq!(report.removed_cuts, [4]);
+        assert!(report.shots.is_empty());
+
+        // Second input ends before the last shot of the first input
+        let report = differ.compare(&other, &generate_metadata(&[1]));
+        assert_eq!(report.frames.other, 1);
+    }
+}
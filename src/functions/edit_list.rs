@@ -0,0 +1,271 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+
+use crate::cmv40::EditRate;
+use crate::functions::TimecodeFormat;
+
+/// Record ranges of events in a CMX3600 EDL, in frames of record timecode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditList {
+    /// Format of the last `FCM` in EDL.
+    pub format: TimecodeFormat,
+    pub events: Vec<Range<usize>>,
+}
+
+/// How cuts in EDL are used with cuts in RPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdlMode {
+    /// Only cuts in EDL start a shot.
+    #[default]
+    Replace,
+    /// Cuts in both EDL and RPU start a shot.
+    Merge,
+}
+
+/// Shot boundaries from an EDL, as frame indices in timeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdlCuts {
+    /// Sorted without duplicates.
+    pub frames: Vec<usize>,
+    pub mode: EdlMode,
+}
+
+/// A frame where cuts in RPU and EDL disagree, by frame index in timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutMismatch {
+    /// A cut in EDL without a cut in RPU.
+    MissingInRpu(usize),
+    /// A cut in RPU without a cut in EDL.
+    MissingInEdl(usize),
+}
+
+impl EditList {
+    pub fn from_file<P: AsRef<Path>>(path: P, edit_rate: EditRate) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+
+        Self::parse(&content, edit_rate).with_context(|| format!("Invalid EDL {}", path.display()))
+    }
+
+    /// Parse events with record timecode at the frame rate,
+    /// drop-frame timecode is used after `FCM: DROP FRAME`.
+    pub fn parse(content: &str, edit_rate: EditRate) -> Result<Self> {
+        let mut format = TimecodeFormat::new(edit_rate, false)?;
+        let mut events = Vec::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+
+            if let Some(fcm) = line.strip_prefix("FCM:") {
+                let drop_frame = fcm.trim().eq_ignore_ascii_case("DROP FRAME");
+                format = TimecodeFormat::new(edit_rate, drop_frame)?;
+                continue;
+            }
+
+            // Event lines start with the event number, comments and notes are ignored
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            if !tokens
+                .first()
+                .is_some_and(|t| t.chars().all(|c| c.is_ascii_digit()))
+            {
+                continue;
+            }
+
+            // Source in, source out, record in, record out
+            let timecodes = tokens
+                .iter()
+                .filter(|t| t.contains([':', ';']))
+                .collect::<Vec<_>>();
+            ensure!(timecodes.len() == 4, "Invalid event at line {}.", i + 1);
+
+            let record_in = format
+                .parse(timecodes[2])
+                .with_context(|| format!("Invalid record in at line {}.", i + 1))?;
+            let record_out = format
+                .parse(timecodes[3])
+                .with_context(|| format!("Invalid record out at line {}.", i + 1))?;
+            ensure!(
+                record_in <= record_out,
+                "Record out is before record in at line {}.",
+                i + 1
+            );
+
+            events.push(record_in..record_out);
+        }
+
+        ensure!(!events.is_empty(), "No event found.");
+
+        Ok(Self { format, events })
+    }
+
+    /// Record in of the first event in timeline.
+    pub fn start(&self) -> usize {
+        self.events
+            .iter()
+            .map(|e| e.start)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Cuts at record in and out points of all events, where the record timecode `start` is
+    /// the frame `offset` in timeline. Points before `start` are dropped.
+    pub fn cuts(&self, start: usize, offset: usize, mode: EdlMode) -> EdlCuts {
+        let mut frames = self
+            .events
+            .iter()
+            .flat_map(|e| [e.start, e.end])
+            .filter(|&frame| frame >= start)
+            .map(|frame| frame - start + offset)
+            .collect::<Vec<_>>();
+        frames.sort_unstable();
+        frames.dedup();
+
+        EdlCuts { frames, mode }
+    }
+}
+
+/// Matches cuts in RPU with cuts in EDL, frames must be passed in order.
+#[derive(Debug, Clone)]
+pub(crate) struct EdlCutMatcher {
+    cuts: EdlCuts,
+    // Position in cuts to be checked next
+    next: usize,
+    mismatches: Vec<CutMismatch>,
+}
+
+impl EdlCutMatcher {
+    pub fn new(cuts: EdlCuts) -> Self {
+        Self {
+            cuts,
+            next: 0,
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// Whether the frame at `index` in timeline starts a new shot, with the cut in RPU.
+    /// Disagreements are kept unless the frame is the first one, which always starts a shot.
+    pub fn is_cut(&mut self, index: usize, rpu_cut: bool, is_first: bool) -> bool {
+        let frames = &self.cuts.frames;
+
+        // Cuts of skipped frames
+        while frames.get(self.next).is_some_and(|&frame| frame < index) {
+            self.next += 1;
+        }
+
+        let edl_cut = frames.get(self.next) == Some(&index);
+
+        if !is_first {
+            match (edl_cut, rpu_cut) {
+                (true, false) => self.mismatches.push(CutMismatch::MissingInRpu(index)),
+                (false, true) => self.mismatches.push(CutMismatch::MissingInEdl(index)),
+                _ => (),
+            }
+        }
+
+        match self.cuts.mode {
+            EdlMode::Replace => edl_cut,
+            EdlMode::Merge => edl_cut || rpu_cut,
+        }
+    }
+
+    pub fn take_mismatches(&mut self) -> Vec<CutMismatch> {
+        std::mem::take(&mut self.mismatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+
+    use super::{CutMismatch, EditList, EdlCuts, EdlMode};
+    use crate::cmv40::EditRate;
+    use crate::functions::{ShotOptions, ShotReader};
+
+    const EDL: &str = "TITLE: Test\r\n\
+        FCM: NON-DROP FRAME\r\n\
+        \r\n\
+        001  AX       V     C        00:00:00:00 00:00:01:00 01:00:00:00 01:00:01:00\r\n\
+        * FROM CLIP NAME: A\r\n\
+        002  AX       V     C        00:00:01:00 00:00:01:00 01:00:01:00 01:00:01:00\r\n\
+        002  AX       V     D    012 00:00:05:00 00:00:06:00 01:00:01:00 01:00:02:00\r\n\
+        M2   AX       050.0                      00:00:05:00\r\n\
+        003  BL       V     C        00:00:00:00 00:00:00:12 01:00:02:12 01:00:03:00\r\n";
+
+    #[test]
+    fn test_edit_list() {
+        let edit_list = EditList::parse(EDL, EditRate([24, 1])).unwrap();
+        assert_eq!(edit_list.events.len(), 4);
+        assert_eq!(edit_list.start(), 86400);
+
+        let cuts = edit_list.cuts(edit_list.start(), 0, EdlMode::Replace);
+        assert_eq!(cuts.frames, [0, 24, 48, 60, 72]);
+
+        let cuts = edit_list.cuts(86400 + 24, 10, EdlMode::Merge);
+        assert_eq!(cuts.frames, [10, 34, 46, 58]);
+
+        let drop_frame = EDL.replace("NON-DROP FRAME", "DROP FRAME");
+        assert!(EditList::parse(&drop_frame, EditRate([24, 1])).is_err());
+        let edit_list = EditList::parse(&drop_frame, EditRate([30000, 1001])).unwrap();
+        assert!(edit_list.format.drop_frame);
+        assert_eq!(edit_list.start(), 107892);
+
+        assert!(EditList::parse("TITLE: Empty\r\n", EditRate([24, 1])).is_err());
+    }
+
+    #[test]
+    fn test_edl_cuts() {
+        // Shots in RPU: 0..4, 4..10
+        let config = GenerateConfig {
+            length: 10,
+            shots: vec![
+                VideoShot {
+                    duration: 4,
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 6,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let read_shots = |mode| {
+            let rpus = config.generate_rpu_list().unwrap().into_iter().map(Ok);
+            let options = ShotOptions {
+                offset: 100,
+                ..Default::default()
+            };
+            let cuts = EdlCuts {
+                frames: vec![100, 106, 110],
+                mode,
+            };
+
+            let mut shot_reader = ShotReader::new(rpus, options).with_edl_cuts(cuts);
+            let shots = shot_reader
+                .by_ref()
+                .map(|shot| shot.map(|shot| shot.record.in_))
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+
+            (shots, shot_reader.take_cut_mismatches())
+        };
+
+        let (shots, mismatches) = read_shots(EdlMode::Replace);
+        assert_eq!(shots, [100, 106]);
+        assert_eq!(
+            mismatches,
+            [
+                CutMismatch::MissingInEdl(104),
+                CutMismatch::MissingInRpu(106)
+            ]
+        );
+
+        let (shots, _) = read_shots(EdlMode::Merge);
+        assert_eq!(shots, [100, 104, 106]);
+    }
+}
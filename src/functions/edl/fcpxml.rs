@@ -0,0 +1,81 @@
+use std::io::Write;
+
+use anyhow::Result;
+use quick_xml::escape::escape;
+
+use super::Timeline;
+
+impl Timeline {
+    /// Final Cut Pro XML with the clip as an asset in a single sequence.
+    /// The asset has no media file, to be relinked in NLE.
+    pub(super) fn write_fcpxml<W: Write>(&self, mut writer: W) -> Result<()> {
+        let [n, d] = self.edit_rate.0;
+        let name = escape(&self.name);
+        let clip_name = escape(&self.clip_name);
+        let tc_format = if self.format.drop_frame { "DF" } else { "NDF" };
+        let timeline_duration = self.events.last().map_or(0, |event| event.frame_out);
+
+        write!(
+            writer,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE fcpxml>\n\
+             <fcpxml version=\"1.8\">\n\
+             \x20   <resources>\n\
+             \x20       <format id=\"r1\" frameDuration=\"{d}/{n}s\"/>\n\
+             \x20       <asset id=\"r2\" name=\"{clip_name}\" start=\"0s\" duration=\"{}\" hasVideo=\"1\" format=\"r1\"/>\n\
+             \x20   </resources>\n\
+             \x20   <library>\n\
+             \x20       <event name=\"{name}\">\n\
+             \x20           <project name=\"{name}\">\n\
+             \x20               <sequence format=\"r1\" duration=\"{}\" tcStart=\"{}\" tcFormat=\"{tc_format}\">\n\
+             \x20                   <spine>\n",
+            self.time(self.duration),
+            self.time(timeline_duration),
+            self.time(self.start_frame),
+        )?;
+
+        for (event, frame_in, frame_out) in self.clips() {
+            if let Some(dissolve) = event.dissolve {
+                writeln!(
+                    writer,
+                    "                        <transition name=\"Cross Dissolve\" offset=\"{}\" duration=\"{}\"/>",
+                    self.time(self.start_frame + dissolve.start),
+                    self.time(dissolve.duration)
+                )?;
+            }
+
+            let offset = self.time(self.start_frame + frame_in);
+
+            writeln!(
+                writer,
+                "                        <asset-clip ref=\"r2\" name=\"{clip_name}\" offset=\"{offset}\" start=\"{}\" duration=\"{}\" tcFormat=\"{tc_format}\"/>",
+                self.time(frame_in),
+                self.time(frame_out - frame_in)
+            )?;
+        }
+
+        write!(
+            writer,
+            "                    </spine>\n\
+             \x20               </sequence>\n\
+             \x20           </project>\n\
+             \x20       </event>\n\
+             \x20   </library>\n\
+             </fcpxml>\n"
+        )?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Rational time in seconds of a frame count.
+    fn time(&self, frames: usize) -> String {
+        let [n, d] = self.edit_rate.0;
+
+        if frames == 0 {
+            "0s".to_string()
+        } else {
+            format!("{}/{n}s", frames * d)
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{stdin, BufWriter, Write};
+use std::iter;
+use std::path::Path;
+
+use crate::commands::edl::{EdlArgs, EdlFormatType};
+use crate::functions::{
+    CutDetector, CutThresholds, Dissolve, DissolveDetector, RpuReader, TimecodeFormat,
+};
+use anyhow::{bail, ensure, Result};
+
+use crate::cmv40::EditRate;
+
+mod fcpxml;
+mod otio;
+mod scene_cuts;
+
+#[derive(Debug, Default)]
+pub struct EdlConverter {
+    frame_index: usize,
+    shots: Vec<usize>,
+}
+
+// Maximum number of events in CMX3600
+const EVENTS_PER_FILE: usize = 9999;
+
+impl EdlFormatType {
+    /// Format by file extension of output, CMX3600 if unknown.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("otio") => Self::Otio,
+            Some(ext) if ext.eq_ignore_ascii_case("fcpxml") => Self::Fcpxml,
+            Some(ext) if ext.eq_ignore_ascii_case("xml") => Self::Chapters,
+            _ => Self::Cmx3600,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Cmx3600 => "edl",
+            Self::Otio => "otio",
+            Self::Fcpxml => "fcpxml",
+            Self::Chapters => "xml",
+            Self::Qpfile | Self::Ffmpeg | Self::Frames => "txt",
+        }
+    }
+}
+
+/// Shots of the input as one clip in a timeline, shared by all output formats.
+#[derive(Debug)]
+struct Timeline {
+    name: String,
+    clip_name: String,
+    edit_rate: EditRate,
+    format: TimecodeFormat,
+    // First frame of timeline in record timecode
+    start_frame: usize,
+    // Number of frames in the clip
+    duration: usize,
+    // First frame of each shot
+    cuts: Vec<usize>,
+    events: Vec<Event>,
+}
+
+/// Shot in the timeline, as an event of CMX3600.
+/// With a dissolve from the previous event, the event starts at the first frame of the dissolve
+/// and the previous event ends there. Formats where a transition overlaps both clips
+/// split them at the cut in RPU instead, see [`Timeline::clips`].
+#[derive(Debug)]
+struct Event {
+    frame_in: usize,
+    frame_out: usize,
+    dissolve: Option<Dissolve>,
+}
+
+impl EdlConverter {
+    pub fn convert(args: EdlArgs) -> Result<()> {
+        let input = match args.input {
+            Some(input) => input,
+            None => bail!("No input file provided."),
+        };
+
+        ensure!(args.count != Some(0), "Invalid specified frame count.");
+
+        ensure!(
+            args.rate.len() <= 2,
+            "Invalid frame rate. Use '/' as delimiter if needed, like 24 or 24000/1001"
+        );
+
+        // We do not need to consider playback time here, so NDF is used unless DF is requested.
+        let edit_rate = EditRate::from(args.rate).normalize();
+        let format = TimecodeFormat::new(edit_rate, args.drop_frame)?;
+        let start_frame = format.parse(&args.start_timecode)?;
+
+        let cut_detection = CutThresholds::from_args(&args.cut_detection);
+        if let Some(cut_detection) = &cut_detection {
+            cut_detection.validate()?;
+        }
+
+        println!("Parsing RPU file...");
+
+        let mut count = args.count.unwrap_or(usize::MAX);
+
+        let mut edl = EdlConverter::default();
+        let mut cut_detector = cut_detection.map(CutDetector::new);
+        let mut dissolve_detector = args.detect_dissolves.then(DissolveDetector::default);
+
+        // Only the index of each shot is kept, RPUs are dropped once parsed.
+        for rpu in RpuReader::open(&input)?.skip(args.skip) {
+            if count == 0 {
+                break;
+            }
+
+            if let Some(ref vdr) = rpu?.vdr_dm_data {
+                let is_cut = match cut_detector {
+                    Some(ref mut detector) => detector.is_cut(vdr),
+                    None => vdr.scene_refresh_flag == 1,
+                };
+
+                if is_cut {
+                    edl.shots.push(edl.frame_index);
+                }
+
+                if let Some(ref mut detector) = dissolve_detector {
+                    detector.update(edl.frame_index, vdr, is_cut);
+                }
+            }
+
+            edl.frame_index += 1;
+            count -= 1;
+        }
+
+        ensure!(edl.frame_index > 0, "No RPU found.");
+
+        if args.count.is_some() && count > 0 {
+            println!("Specified frame count exceeds the end.");
+        }
+
+        if edl.shots.len() == edl.frame_index && edl.frame_index > 1 && !args.force {
+            println!("Per-frame rpu detected, use --detect-cuts to detect cuts from metadata.");
+            println!("No need to generate EDL without it. Do you want to proceed? (Y/n)");
+
+            let mut input = String::new();
+            stdin().read_line(&mut input)?;
+
+            if input.trim().to_lowercase() != "y" {
+                println!("Aborted.");
+                return Ok(());
+            }
+        }
+
+        let dissolves = dissolve_detector
+            .map(|detector| detector.finish(edl.frame_index))
+            .unwrap_or_default();
+
+        if !dissolves.is_empty() {
+            println!("Detected {} dissolve(s).", dissolves.len());
+        }
+
+        // Last frame
+        edl.shots.push(edl.frame_index);
+
+        let output_format = args.format.unwrap_or_else(|| match &args.output {
+            Some(output) => EdlFormatType::from_path(output),
+            None => EdlFormatType::Cmx3600,
+        });
+
+        let output = if let Some(output) = args.output {
+            output
+        } else {
+            let file_name = format!("metadata.{}", output_format.extension());
+            println!("No output file provided, writing to {file_name} at current path...");
+            file_name.into()
+        };
+
+        let timeline = Timeline {
+            // TODO: rename
+            name: format!("Timeline {}", input.file_stem().unwrap().to_string_lossy()),
+            clip_name: args.clip_name,
+            edit_rate,
+            format,
+            start_frame,
+            duration: edl.frame_index,
+            cuts: edl.cuts(),
+            events: edl.events(dissolves),
+        };
+
+        match output_format {
+            EdlFormatType::Cmx3600 => timeline.write_cmx3600(&output),
+            EdlFormatType::Otio => timeline.write_otio(BufWriter::new(File::create(output)?)),
+            EdlFormatType::Fcpxml => timeline.write_fcpxml(BufWriter::new(File::create(output)?)),
+            EdlFormatType::Qpfile => timeline.write_qpfile(BufWriter::new(File::create(output)?)),
+            EdlFormatType::Ffmpeg => timeline.write_ffmpeg(BufWriter::new(File::create(output)?)),
+            EdlFormatType::Chapters => {
+                timeline.write_chapters(BufWriter::new(File::create(output)?))
+            }
+            EdlFormatType::Frames => timeline.write_frames(BufWriter::new(File::create(output)?)),
+        }
+    }
+
+    /// First frame of each shot, the first frame is always a cut.
+    fn cuts(&self) -> Vec<usize> {
+        let cuts = self
+            .shots
+            .iter()
+            .copied()
+            .filter(|&shot| shot > 0 && shot < self.frame_index);
+
+        iter::once(0).chain(cuts).collect()
+    }
+
+    /// Events between shots, the previous event ends at the start of a dissolve.
+    fn events(&self, dissolves: Vec<Dissolve>) -> Vec<Event> {
+        let mut dissolves = dissolves.into_iter().peekable();
+        let mut events = Vec::with_capacity(self.shots.len());
+
+        let mut frame_in = 0;
+        let mut dissolve = None;
+
+        for &shot in &self.shots {
+            if shot == 0 {
+                continue;
+            }
+
+            let next_dissolve = dissolves.next_if(|d| d.cut == shot);
+            let frame_out = next_dissolve.map_or(shot, |d| d.start);
+
+            events.push(Event {
+                frame_in,
+                frame_out,
+                dissolve,
+            });
+
+            dissolve = next_dissolve;
+            frame_in = frame_out;
+        }
+
+        events
+    }
+}
+
+impl Event {
+    /// First frame of the shot in RPU.
+    fn cut(&self) -> usize {
+        self.dissolve.map_or(self.frame_in, |d| d.cut)
+    }
+}
+
+impl Timeline {
+    /// Events with frame in and out at cuts in RPU.
+    /// A dissolve spans both clips, `cut - start` frames before the cut and the rest after it.
+    fn clips(&self) -> impl Iterator<Item = (&Event, usize, usize)> {
+        self.events.iter().enumerate().map(|(i, event)| {
+            let frame_out = self.events.get(i + 1).map_or(event.frame_out, Event::cut);
+            (event, event.cut(), frame_out)
+        })
+    }
+
+    /// CMX3600 is limited to 9999 events,
+    /// so long timelines are split into files with a suffix added to the file name.
+    fn write_cmx3600(&self, output: &Path) -> Result<()> {
+        let fcm = if self.format.drop_frame {
+            "DROP FRAME"
+        } else {
+            "NON-DROP FRAME"
+        };
+
+        let chunks = self.events.chunks(EVENTS_PER_FILE);
+        let is_single_output = chunks.len() == 1;
+
+        for (i, chunk) in chunks.enumerate() {
+            let output = if is_single_output {
+                output.to_path_buf()
+            } else {
+                let prefix = output.file_stem().unwrap().to_string_lossy();
+                let extension = output
+                    .extension()
+                    .unwrap_or(OsStr::new("edl"))
+                    .to_string_lossy();
+
+                output.with_file_name(format!("{prefix}_{i}.{extension}"))
+            };
+
+            let mut writer = BufWriter::new(File::create(output)?);
+
+            write!(writer, "TITLE: {} {i}\r\nFCM: {fcm}\r\n\r\n", self.name)?;
+
+            for (j, event) in chunk.iter().enumerate() {
+                let tc_source_in = self.format.timecode(event.frame_in);
+                let tc_source_out = self.format.timecode(event.frame_out);
+                let tc_record_in = self.format.timecode(self.start_frame + event.frame_in);
+                let tc_record_out = self.format.timecode(self.start_frame + event.frame_out);
+
+                let k = j + 1;
+
+                match event.dissolve {
+                    None => write!(
+                        writer,
+                        "{:>04}  AX       V     C        {} {} {} {}  \r\n* FROM CLIP NAME: {}\r\n\r\n",
+                        k,
+                        tc_source_in,
+                        tc_source_out,
+                        tc_record_in,
+                        tc_record_out,
+                        self.clip_name
+                    )?,
+                    // Outgoing clip with zero duration, then the transition to incoming clip
+                    Some(Dissolve { duration, .. }) => write!(
+                        writer,
+                        "{:>04}  AX       V     C        {} {} {} {}  \r\n\
+                         {:>04}  AX       V     D    {:>03} {} {} {} {}  \r\n\
+                         * FROM CLIP NAME: {}\r\n* TO CLIP NAME: {}\r\n\r\n",
+                        k,
+                        tc_source_in,
+                        tc_source_in,
+                        tc_record_in,
+                        tc_record_in,
+                        k,
+                        duration,
+                        tc_source_in,
+                        tc_source_out,
+                        tc_record_in,
+                        tc_record_out,
+                        self.clip_name,
+                        self.clip_name
+                    )?,
+                }
+            }
+
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EdlConverter, Event, Timeline};
+    use crate::cmv40::EditRate;
+    use crate::functions::{Dissolve, TimecodeFormat};
+
+    fn timeline() -> Timeline {
+        let edit_rate = EditRate([24000, 1001]);
+
+        Timeline {
+            name: "Timeline".to_string(),
+            clip_name: "A&B".to_string(),
+            edit_rate,
+            format: TimecodeFormat::new(edit_rate, false).unwrap(),
+            start_frame: 86400,
+            duration: 10,
+            cuts: vec![0, 4],
+            events: vec![
+                Event {
+                    frame_in: 0,
+                    frame_out: 3,
+                    dissolve: None,
+                },
+                Event {
+                    frame_in: 3,
+                    frame_out: 10,
+                    dissolve: Some(Dissolve {
+                        start: 3,
+                        cut: 4,
+                        duration: 3,
+                    }),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_otio() {
+        let mut buffer = Vec::new();
+        timeline().write_otio(&mut buffer).unwrap();
+
+        let otio: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(otio["global_start_time"]["value"], 86400.0);
+
+        let children = otio["tracks"]["children"][0]["children"]
+            .as_array()
+            .unwrap();
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[0]["source_range"]["duration"]["value"], 4.0);
+        assert_eq!(children[1]["OTIO_SCHEMA"], "Transition.1");
+        assert_eq!(children[1]["in_offset"]["value"], 1.0);
+        assert_eq!(children[1]["out_offset"]["value"], 2.0);
+        assert_eq!(children[2]["source_range"]["start_time"]["value"], 4.0);
+        assert_eq!(children[2]["source_range"]["duration"]["value"], 6.0);
+    }
+
+    #[test]
+    fn test_fcpxml() {
+        let mut buffer = Vec::new();
+        timeline().write_fcpxml(&mut buffer).unwrap();
+
+        let fcpxml = String::from_utf8(buffer).unwrap();
+        assert!(fcpxml.contains("tcStart=\"86486400/24000s\""));
+        assert!(fcpxml.contains(
+            "<asset-clip ref=\"r2\" name=\"A&amp;B\" offset=\"86490404/24000s\" start=\"4004/24000s\" duration=\"6006/24000s\""
+        ));
+        assert!(fcpxml.contains("<transition name=\"Cross Dissolve\" offset=\"86489403/24000s\" duration=\"3003/24000s\"/>"));
+    }
+
+    #[test]
+    fn test_scene_cuts() {
+        let timeline = timeline();
+
+        let mut buffer = Vec::new();
+        timeline.write_qpfile(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "0 I\n4 I\n");
+
+        let mut buffer = Vec::new();
+        timeline.write_ffmpeg(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "0.000000,0.166833\n");
+
+        let mut buffer = Vec::new();
+        timeline.write_chapters(&mut buffer).unwrap();
+        let chapters = String::from_utf8(buffer).unwrap();
+        assert!(chapters.contains("<ChapterTimeStart>00:00:00.166833333</ChapterTimeStart>"));
+        assert!(chapters.contains("<ChapterTimeEnd>00:00:00.417083333</ChapterTimeEnd>"));
+    }
+
+    #[test]
+    fn test_dissolve_events() {
+        let edl = EdlConverter {
+            frame_index: 10,
+            shots: vec![0, 6, 10],
+        };
+        let dissolve = Dissolve {
+            start: 3,
+            cut: 6,
+            duration: 6,
+        };
+
+        let events = edl.events(vec![dissolve]);
+        let boundaries = events
+            .iter()
+            .map(|event| (event.frame_in, event.frame_out, event.dissolve))
+            .collect::<Vec<_>>();
+        assert_eq!(boundaries, [(0, 3, None), (3, 10, Some(dissolve))]);
+
+        let timeline = Timeline {
+            events,
+            ..timeline()
+        };
+
+        // Clips are split at the cut, the transition spans 3 frames of each
+        let mut buffer = Vec::new();
+        timeline.write_otio(&mut buffer).unwrap();
+
+        let otio: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        let children = otio["tracks"]["children"][0]["children"]
+            .as_array()
+            .unwrap();
+        assert_eq!(children[0]["source_range"]["duration"]["value"], 6.0);
+        assert_eq!(children[1]["in_offset"]["value"], 3.0);
+        assert_eq!(children[1]["out_offset"]["value"], 3.0);
+        assert_eq!(children[2]["source_range"]["start_time"]["value"], 6.0);
+
+        let mut buffer = Vec::new();
+        timeline.write_fcpxml(&mut buffer).unwrap();
+
+        // 86403 frames in 24000/1001
+        let fcpxml = String::from_utf8(buffer).unwrap();
+        assert!(fcpxml.contains(
+            "<transition name=\"Cross Dissolve\" offset=\"86489403/24000s\" duration=\"6006/24000s\"/>"
+        ));
+        assert!(fcpxml.contains("offset=\"86492406/24000s\" start=\"6006/24000s\""));
+    }
+}
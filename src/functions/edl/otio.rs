@@ -0,0 +1,99 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use super::Timeline;
+
+impl Timeline {
+    /// OpenTimelineIO JSON with a single video track.
+    /// Media of the clip is a missing reference, to be relinked in NLE.
+    pub(super) fn write_otio<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut children = Vec::with_capacity(self.events.len());
+
+        for (event, frame_in, frame_out) in self.clips() {
+            if let Some(dissolve) = event.dissolve {
+                let in_offset = dissolve.cut - dissolve.start;
+
+                children.push(json!({
+                    "OTIO_SCHEMA": "Transition.1",
+                    "metadata": {},
+                    "name": "",
+                    "transition_type": "SMPTE_Dissolve",
+                    "in_offset": self.rational_time(in_offset),
+                    "out_offset": self.rational_time(dissolve.duration - in_offset),
+                }));
+            }
+
+            children.push(json!({
+                "OTIO_SCHEMA": "Clip.2",
+                "metadata": {},
+                "name": self.clip_name,
+                "source_range": self.time_range(frame_in, frame_out - frame_in),
+                "effects": [],
+                "markers": [],
+                "enabled": true,
+                "media_references": {
+                    "DEFAULT_MEDIA": {
+                        "OTIO_SCHEMA": "MissingReference.1",
+                        "metadata": {},
+                        "name": self.clip_name,
+                        "available_range": self.time_range(0, self.duration),
+                        "available_image_bounds": null,
+                    }
+                },
+                "active_media_reference_key": "DEFAULT_MEDIA",
+            }));
+        }
+
+        let timeline = json!({
+            "OTIO_SCHEMA": "Timeline.1",
+            "metadata": {},
+            "name": self.name,
+            "global_start_time": self.rational_time(self.start_frame),
+            "tracks": {
+                "OTIO_SCHEMA": "Stack.1",
+                "metadata": {},
+                "name": "tracks",
+                "source_range": null,
+                "effects": [],
+                "markers": [],
+                "enabled": true,
+                "children": [{
+                    "OTIO_SCHEMA": "Track.1",
+                    "metadata": {},
+                    "name": "V1",
+                    "source_range": null,
+                    "effects": [],
+                    "markers": [],
+                    "enabled": true,
+                    "kind": "Video",
+                    "children": children,
+                }],
+            },
+        });
+
+        serde_json::to_writer_pretty(&mut writer, &timeline)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn rational_time(&self, frames: usize) -> Value {
+        let [n, d] = self.edit_rate.0;
+
+        json!({
+            "OTIO_SCHEMA": "RationalTime.1",
+            "rate": n as f64 / d as f64,
+            "value": frames as f64,
+        })
+    }
+
+    fn time_range(&self, start: usize, duration: usize) -> Value {
+        json!({
+            "OTIO_SCHEMA": "TimeRange.1",
+            "start_time": self.rational_time(start),
+            "duration": self.rational_time(duration),
+        })
+    }
+}
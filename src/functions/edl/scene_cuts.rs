@@ -0,0 +1,102 @@
+use std::io::Write;
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use super::Timeline;
+
+impl Timeline {
+    /// x265 `--qpfile`, the first frame of each shot is an IDR frame.
+    pub(super) fn write_qpfile<W: Write>(&self, mut writer: W) -> Result<()> {
+        for cut in &self.cuts {
+            writeln!(writer, "{cut} I")?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Timestamps in seconds for ffmpeg `-force_key_frames`, separated by commas.
+    pub(super) fn write_ffmpeg<W: Write>(&self, mut writer: W) -> Result<()> {
+        let timestamps = self
+            .cuts
+            .iter()
+            .map(|&cut| {
+                let micros = self.nanoseconds(cut) / 1000;
+                format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+            })
+            .join(",");
+
+        writeln!(writer, "{timestamps}")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Matroska chapters XML, with a chapter for each shot.
+    pub(super) fn write_chapters<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(
+            writer,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n\
+             <Chapters>\n\
+             \x20   <EditionEntry>\n"
+        )?;
+
+        let ends = self.cuts.iter().skip(1).chain([&self.duration]);
+
+        for (i, (&start, &end)) in self.cuts.iter().zip(ends).enumerate() {
+            write!(
+                writer,
+                "        <ChapterAtom>\n\
+                 \x20           <ChapterTimeStart>{}</ChapterTimeStart>\n\
+                 \x20           <ChapterTimeEnd>{}</ChapterTimeEnd>\n\
+                 \x20           <ChapterDisplay>\n\
+                 \x20               <ChapterString>Shot {}</ChapterString>\n\
+                 \x20               <ChapterLanguage>und</ChapterLanguage>\n\
+                 \x20           </ChapterDisplay>\n\
+                 \x20       </ChapterAtom>\n",
+                self.chapter_time(start),
+                self.chapter_time(end),
+                i + 1
+            )?;
+        }
+
+        write!(writer, "    </EditionEntry>\n</Chapters>\n")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Plain list of the first frame of each shot.
+    pub(super) fn write_frames<W: Write>(&self, mut writer: W) -> Result<()> {
+        for cut in &self.cuts {
+            writeln!(writer, "{cut}")?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // Rounded down, so the time is never after the frame
+    fn nanoseconds(&self, frames: usize) -> u128 {
+        let [n, d] = self.edit_rate.0;
+
+        frames as u128 * d as u128 * 1_000_000_000 / n as u128
+    }
+
+    fn chapter_time(&self, frames: usize) -> String {
+        let nanos = self.nanoseconds(frames);
+        let seconds = nanos / 1_000_000_000;
+
+        format!(
+            "{:02}:{:02}:{:02}.{:09}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            nanos % 1_000_000_000
+        )
+    }
+}
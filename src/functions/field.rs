@@ -0,0 +1,350 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result};
+
+use crate::cmv40::DVDynamicData;
+use crate::metadata::WithTid;
+use crate::{Level2, Level3, Level8, MDFType};
+
+const L1_NAMES: [&str; 3] = ["min", "avg", "max"];
+const TRIM_NAMES: [&str; 6] = [
+    "lift",
+    "gain",
+    "gamma",
+    "chroma_weight",
+    "saturation_gain",
+    "ms_weight",
+];
+const L5_NAMES: [&str; 2] = ["canvas_ar", "image_ar"];
+const VECTOR_NAMES: [&str; 6] = ["red", "yellow", "green", "cyan", "blue", "magenta"];
+
+/// A column of metadata in CSV, with the index of value in its level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Field {
+    Level1(usize),
+    // TID, index of trim
+    Level2(usize, usize),
+    Level3(usize),
+    // Canvas and image aspect ratio
+    Level5(usize),
+    Level8(u8, Level8Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Level8Field {
+    Trim(usize),
+    MidContrastBias,
+    HighlightClipping,
+    Saturation(usize),
+    Hue(usize),
+}
+
+/// Metadata block in RPU which a field is written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Block {
+    Level1,
+    Level2(usize),
+    Level3,
+    Level5,
+    Level8(u8),
+}
+
+impl Field {
+    /// Columns for all metadata found in shots and frames, grouped by level.
+    pub fn columns<'a, I>(data: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = &'a DVDynamicData>,
+    {
+        let mut level2_tids = BTreeSet::new();
+        let mut level8_tids = BTreeSet::new();
+        let mut has_level3 = false;
+
+        for data in data {
+            level2_tids.extend(data.level2.iter().flatten().map(|level2| level2.tid));
+            level8_tids.extend(data.level8.iter().flatten().map(|level8| level8.tid));
+            has_level3 |= data.level3.is_some();
+        }
+
+        let mut columns = (0..3).map(Self::Level1).collect::<Vec<_>>();
+
+        for tid in level2_tids {
+            columns.extend((0..6).map(|i| Self::Level2(tid, i)));
+        }
+
+        if has_level3 {
+            columns.extend((0..3).map(Self::Level3));
+        }
+
+        columns.extend((0..2).map(Self::Level5));
+
+        for tid in level8_tids {
+            let fields = (0..6)
+                .map(Level8Field::Trim)
+                .chain([Level8Field::MidContrastBias, Level8Field::HighlightClipping])
+                .chain((0..6).map(Level8Field::Saturation))
+                .chain((0..6).map(Level8Field::Hue));
+
+            columns.extend(fields.map(|field| Self::Level8(tid, field)));
+        }
+
+        columns
+    }
+
+    pub fn block(&self) -> Block {
+        match *self {
+            Self::Level1(_) => Block::Level1,
+            Self::Level2(tid, _) => Block::Level2(tid),
+            Self::Level3(_) => Block::Level3,
+            Self::Level5(_) => Block::Level5,
+            Self::Level8(tid, _) => Block::Level8(tid),
+        }
+    }
+
+    /// Level 3 and Level 8 only exist in CM v4.0 metadata.
+    pub fn is_cmv40_only(&self) -> bool {
+        matches!(self, Self::Level3(_) | Self::Level8(..))
+    }
+
+    pub fn is_valid(&self, value: f32) -> bool {
+        match self {
+            Self::Level1(_) => (0.0..=1.0).contains(&value),
+            Self::Level5(_) => value.is_finite() && value > 0.0,
+            _ => (-1.0..=1.0).contains(&value),
+        }
+    }
+
+    /// Nearest valid value.
+    pub fn clamp(&self, value: f32) -> f32 {
+        match self {
+            Self::Level1(_) => value.clamp(0.0, 1.0),
+            Self::Level5(_) => value.max(f32::EPSILON),
+            _ => value.clamp(-1.0, 1.0),
+        }
+    }
+
+    pub fn range(&self) -> &'static str {
+        match self {
+            Self::Level1(_) => "0 to 1",
+            Self::Level5(_) => "greater than 0",
+            _ => "-1 to 1",
+        }
+    }
+
+    /// None if the level or target display is not in metadata.
+    pub fn get(&self, data: &DVDynamicData) -> Option<f32> {
+        match *self {
+            Self::Level1(i) => Self::nth(data.level1.image_character, i),
+            Self::Level2(tid, i) => data
+                .level2
+                .iter()
+                .flatten()
+                .find(|level2| level2.tid == tid)
+                .and_then(|level2| Self::nth(level2.trim, i)),
+            Self::Level3(i) => data
+                .level3
+                .as_ref()
+                .and_then(|level3| Self::nth(level3.l1_offset, i)),
+            Self::Level5(i) => data
+                .level5
+                .as_ref()
+                .and_then(|level5| Self::nth(level5.aspect_ratio, i)),
+            Self::Level8(tid, field) => {
+                let level8 = data
+                    .level8
+                    .iter()
+                    .flatten()
+                    .find(|level8| level8.tid == tid)?;
+
+                match field {
+                    Level8Field::Trim(i) => Self::nth(level8.l8_trim, i),
+                    Level8Field::MidContrastBias => Some(level8.mid_contrast_bias),
+                    Level8Field::HighlightClipping => Some(level8.highlight_clipping),
+                    Level8Field::Saturation(i) => Self::nth(level8.sat_vector_field, i),
+                    Level8Field::Hue(i) => Self::nth(level8.hue_vector_field, i),
+                }
+            }
+        }
+    }
+
+    /// Missing levels are added with default values, except Level 5 which is kept as is.
+    pub fn set(&self, data: &mut DVDynamicData, value: f32) {
+        match *self {
+            Self::Level1(i) => Self::replace(&mut data.level1.image_character, i, value),
+            Self::Level2(tid, i) => {
+                let level2 = Self::find_or_insert::<Level2>(&mut data.level2, tid);
+                Self::replace(&mut level2.trim, i, value);
+            }
+            Self::Level3(i) => {
+                let level3 = data.level3.get_or_insert_with(Level3::default);
+                Self::replace(&mut level3.l1_offset, i, value);
+            }
+            Self::Level5(i) => {
+                if let Some(level5) = &mut data.level5 {
+                    Self::replace(&mut level5.aspect_ratio, i, value);
+                }
+            }
+            Self::Level8(tid, field) => {
+                let level8 = Self::find_or_insert::<Level8>(&mut data.level8, tid as usize);
+
+                match field {
+                    Level8Field::Trim(i) => Self::replace(&mut level8.l8_trim, i, value),
+                    Level8Field::MidContrastBias => level8.mid_contrast_bias = value,
+                    Level8Field::HighlightClipping => level8.highlight_clipping = value,
+                    Level8Field::Saturation(i) => {
+                        Self::replace(&mut level8.sat_vector_field, i, value)
+                    }
+                    Level8Field::Hue(i) => Self::replace(&mut level8.hue_vector_field, i, value),
+                }
+            }
+        }
+    }
+
+    fn nth<T>(values: MDFType<T>, i: usize) -> Option<f32>
+    where
+        T: IntoIterator<Item = f32>,
+    {
+        values.into_inner().into_iter().nth(i)
+    }
+
+    fn replace<T>(values: &mut MDFType<T>, i: usize, value: f32)
+    where
+        T: Copy + IntoIterator<Item = f32> + TryFrom<Vec<f32>>,
+    {
+        let mut new_values = values.into_inner().into_iter().collect::<Vec<_>>();
+        new_values[i] = value;
+
+        if let Ok(new_values) = T::try_from(new_values) {
+            *values = values.with_new_inner(new_values);
+        }
+    }
+
+    fn find_or_insert<T: WithTid>(levels: &mut Option<Vec<T>>, tid: usize) -> &mut T {
+        let levels = levels.get_or_insert_with(Vec::new);
+
+        let index = match levels.iter().position(|level| level.tid() == tid) {
+            Some(index) => index,
+            None => {
+                levels.push(T::with_tid(tid));
+                levels.len() - 1
+            }
+        };
+
+        &mut levels[index]
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Level1(i) => write!(f, "l1_{}", L1_NAMES[i]),
+            Self::Level2(tid, i) => write!(f, "l2_{tid}_{}", TRIM_NAMES[i]),
+            Self::Level3(i) => write!(f, "l3_{}_offset", L1_NAMES[i]),
+            Self::Level5(i) => write!(f, "l5_{}", L5_NAMES[i]),
+            Self::Level8(tid, field) => match field {
+                Level8Field::Trim(i) => write!(f, "l8_{tid}_{}", TRIM_NAMES[i]),
+                Level8Field::MidContrastBias => write!(f, "l8_{tid}_mid_contrast_bias"),
+                Level8Field::HighlightClipping => write!(f, "l8_{tid}_highlight_clipping"),
+                Level8Field::Saturation(i) => write!(f, "l8_{tid}_sat_{}", VECTOR_NAMES[i]),
+                Level8Field::Hue(i) => write!(f, "l8_{tid}_hue_{}", VECTOR_NAMES[i]),
+            },
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let position = |names: &[&str], name: &str| names.iter().position(|n| *n == name);
+
+        let parse = || {
+            let (level, name) = s.split_once('_')?;
+
+            match level {
+                "l1" => position(&L1_NAMES, name).map(Self::Level1),
+                "l2" => {
+                    let (tid, name) = name.split_once('_')?;
+                    Some(Self::Level2(
+                        tid.parse().ok()?,
+                        position(&TRIM_NAMES, name)?,
+                    ))
+                }
+                "l3" => position(&L1_NAMES, name.strip_suffix("_offset")?).map(Self::Level3),
+                "l5" => position(&L5_NAMES, name).map(Self::Level5),
+                "l8" => {
+                    let (tid, name) = name.split_once('_')?;
+
+                    let field = match name {
+                        "mid_contrast_bias" => Level8Field::MidContrastBias,
+                        "highlight_clipping" => Level8Field::HighlightClipping,
+                        _ => match (name.strip_prefix("sat_"), name.strip_prefix("hue_")) {
+                            (Some(color), _) => {
+                                Level8Field::Saturation(position(&VECTOR_NAMES, color)?)
+                            }
+                            (_, Some(color)) => Level8Field::Hue(position(&VECTOR_NAMES, color)?),
+                            _ => Level8Field::Trim(position(&TRIM_NAMES, name)?),
+                        },
+                    };
+
+                    Some(Self::Level8(tid.parse().ok()?, field))
+                }
+                _ => None,
+            }
+        };
+
+        parse().with_context(|| format!("Unknown column {s} in CSV."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, Level8Field};
+    use crate::cmv40::DVDynamicData;
+
+    #[test]
+    fn test_names() {
+        let data = DVDynamicData {
+            level8: Some(vec![crate::Level8 {
+                level: 8,
+                tid: 48,
+                l8_trim: Default::default(),
+                mid_contrast_bias: 0.0,
+                highlight_clipping: 0.0,
+                sat_vector_field: Default::default(),
+                hue_vector_field: Default::default(),
+            }]),
+            ..Default::default()
+        };
+
+        let columns = Field::columns([&data]);
+        assert_eq!(columns.len(), 3 + 2 + 20);
+
+        for column in columns {
+            assert_eq!(column.to_string().parse::<Field>().unwrap(), column);
+        }
+
+        assert_eq!(
+            "l8_48_hue_magenta".parse::<Field>().unwrap(),
+            Field::Level8(48, Level8Field::Hue(5))
+        );
+        assert!("l2_1_slope".parse::<Field>().is_err());
+    }
+
+    #[test]
+    fn test_set() {
+        let mut data = DVDynamicData::default();
+
+        let field = Field::Level2(1, 2);
+        assert_eq!(field.get(&data), None);
+
+        field.set(&mut data, -0.25);
+        assert_eq!(field.get(&data), Some(-0.25));
+        assert_eq!(Field::Level2(1, 0).get(&data), Some(0.0));
+
+        // Level 5 is not added without aspect ratio of canvas
+        Field::Level5(1).set(&mut data, 2.39);
+        assert!(data.level5.is_none());
+    }
+}
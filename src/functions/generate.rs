@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Result};
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::{
+    ExtMetadataBlock, ExtMetadataBlockLevel10, ExtMetadataBlockLevel6,
+};
+use dolby_vision::rpu::generate::{GenerateConfig, GenerateProfile, ShotFrameEdit, VideoShot};
+use dolby_vision::rpu::vdr_dm_data::CmVersion;
+
+use crate::cmv40::{Characteristics, DVDynamicData, ShotPluginNode, Track};
+use crate::commands::generate::{GenerateArgs, Profile};
+use crate::display::{l2pq, PREDEFINED_TARGET_DISPLAYS};
+use crate::functions::input::Input;
+use crate::{rpu_pq_u12_from_f32, IntoCMV40, Level5, Level9, MDFDocument};
+
+const OUT_NAL_HEADER: &[u8] = &[0, 0, 0, 1];
+
+#[derive(Debug)]
+pub struct Generator {
+    canvas: (usize, usize),
+    cm_version: CmVersion,
+    // Target display ID -> target_max_pq
+    targets_map: HashMap<usize, u16>,
+    has_level11: bool,
+}
+
+impl Generator {
+    pub fn generate(args: GenerateArgs) -> Result<()> {
+        let input = match args.input {
+            Some(input) => input,
+            None => bail!("No input file provided."),
+        };
+
+        let canvas = Input::canvas(&args.size)?;
+
+        println!("Parsing XML file...");
+
+        let mdf = MDFDocument::from_file(input)?;
+        let rpus = Self::generate_rpus(mdf, canvas, args.profile.into())?;
+
+        let output = match args.output {
+            Some(output) => output,
+            None => PathBuf::from("RPU.bin"),
+        };
+
+        Self::write_rpus(output, &rpus)?;
+
+        println!("Done. {} frame(s) generated.", rpus.len());
+
+        Ok(())
+    }
+
+    /// RPUs of the first track in the first output.
+    pub(crate) fn generate_rpus(
+        mdf: MDFDocument,
+        canvas: (usize, usize),
+        profile: GenerateProfile,
+    ) -> Result<Vec<DoviRpu>> {
+        let mdf = mdf.into_cmv40();
+
+        let Some(output) = mdf.outputs.outputs.into_iter().next() else {
+            bail!("No output in XML.");
+        };
+
+        let image_ar = output.image_aspect_ratio;
+
+        let Some(track) = output.video.tracks.into_iter().next() else {
+            bail!("No track in output.");
+        };
+
+        println!("Generating RPU...");
+
+        let cm_version = if track.plugin_node.level254.is_some() {
+            CmVersion::V40
+        } else {
+            CmVersion::V29
+        };
+
+        let targets_map = track
+            .plugin_node
+            .dv_global_data
+            .target_displays
+            .iter()
+            .flatten()
+            .map(|d| (d.id, Self::rpu_pq_u12_from_nits(d.peak_brightness as f32)))
+            .collect::<HashMap<_, _>>();
+
+        // Frames of shots with shot-level Level 11, when it's not in track-level
+        let first_in = track
+            .shots
+            .iter()
+            .flatten()
+            .map(|shot| shot.record.in_)
+            .min();
+        let level11_frames = track
+            .shots
+            .iter()
+            .flatten()
+            .filter(|shot| shot.plugin_node.level11.is_some())
+            .map(|shot| {
+                let start = shot.record.in_ - first_in.unwrap_or(0);
+                start..start + shot.record.duration
+            })
+            .collect::<Vec<_>>();
+
+        let generator = Generator {
+            canvas,
+            cm_version,
+            targets_map,
+            has_level11: track.plugin_node.level11.is_some(),
+        };
+
+        let mut config = GenerateConfig {
+            cm_version,
+            profile,
+            level5: Level5::from(image_ar).to_block_with_canvas(canvas),
+            ..Default::default()
+        };
+
+        generator.update_config(&mut config, track)?;
+
+        let mut rpus = config.generate_rpu_list()?;
+
+        if !generator.has_level11 {
+            // Level 11 is always inserted by default
+            rpus.iter_mut()
+                .enumerate()
+                .filter(|(i, _)| !level11_frames.iter().any(|frames| frames.contains(i)))
+                .filter_map(|(_, rpu)| rpu.vdr_dm_data.as_mut())
+                .for_each(|vdr| vdr.remove_metadata_level(11));
+        }
+
+        Ok(rpus)
+    }
+
+    /// Bare RPUs with start codes, like extracted by dovi_tool.
+    pub(crate) fn write_rpus<P: AsRef<Path>>(output: P, rpus: &[DoviRpu]) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(output)?);
+
+        for rpu in rpus {
+            let encoded_rpu = rpu.write_hevc_unspec62_nalu()?;
+
+            writer.write_all(OUT_NAL_HEADER)?;
+            // Remove 0x7C01
+            writer.write_all(&encoded_rpu[2..])?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn update_config(&self, config: &mut GenerateConfig, track: Track) -> Result<()> {
+        let mastering_display = &track.plugin_node.dv_global_data.mastering_display;
+
+        config.source_min_pq = Some(Self::rpu_pq_u12_from_nits(
+            mastering_display.minimum_brightness,
+        ));
+        config.source_max_pq = Some(Self::rpu_pq_u12_from_nits(
+            mastering_display.peak_brightness as f32,
+        ));
+
+        let (max_content_light_level, max_frame_average_light_level) = track
+            .level6
+            .as_ref()
+            .map(|l6| (l6.max_cll as u16, l6.max_fall as u16))
+            .unwrap_or_default();
+
+        config.level6 = Some(ExtMetadataBlockLevel6 {
+            max_display_mastering_luminance: mastering_display.peak_brightness as u16,
+            min_display_mastering_luminance: (mastering_display.minimum_brightness * 10000.0)
+                .round() as u16,
+            max_content_light_level,
+            max_frame_average_light_level,
+        });
+
+        if self.cm_version == CmVersion::V40 {
+            config.level254 = track.plugin_node.level254.as_ref().map(|l| l.into());
+
+            let primaries = mastering_display
+                .primaries
+                .with_white_point(mastering_display.white_point);
+            config
+                .default_metadata_blocks
+                .push(ExtMetadataBlock::Level9((&Level9::from(primaries)).into()));
+
+            if let Some(level11) = &track.plugin_node.level11 {
+                config
+                    .default_metadata_blocks
+                    .push(ExtMetadataBlock::Level11(level11.into()));
+            }
+
+            if let Some(targets) = &track.plugin_node.dv_global_data.target_displays {
+                config.default_metadata_blocks.extend(
+                    targets
+                        .iter()
+                        .filter_map(|d| self.custom_target_block(d))
+                        .map(ExtMetadataBlock::Level10),
+                );
+            }
+        }
+
+        let mut shots = track.shots.unwrap_or_default();
+        shots.sort_by_key(|shot| shot.record.in_);
+
+        let mut next_in = shots.first().map(|shot| shot.record.in_).unwrap_or(0);
+
+        for shot in shots {
+            ensure!(
+                shot.record.in_ == next_in,
+                "Shots are not contiguous at frame {}.",
+                shot.record.in_
+            );
+            ensure!(
+                shot.record.duration > 0,
+                "Invalid duration for shot at frame {}.",
+                shot.record.in_
+            );
+
+            next_in += shot.record.duration;
+
+            let frame_edits = shot
+                .frames
+                .iter()
+                .flatten()
+                .map(|frame| {
+                    ensure!(
+                        frame.edit_offset < shot.record.duration,
+                        "Invalid edit offset {} for shot at frame {}.",
+                        frame.edit_offset,
+                        shot.record.in_
+                    );
+
+                    Ok(ShotFrameEdit {
+                        edit_offset: frame.edit_offset,
+                        metadata_blocks: self.metadata_blocks(&frame.plugin_node)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            config.shots.push(VideoShot {
+                start: shot.record.in_,
+                duration: shot.record.duration,
+                metadata_blocks: self.metadata_blocks(&shot.plugin_node)?,
+                frame_edits,
+                ..Default::default()
+            });
+
+            config.length += shot.record.duration;
+        }
+
+        ensure!(config.length > 0, "No shot in track.");
+
+        Ok(())
+    }
+
+    fn metadata_blocks(&self, plugin_node: &ShotPluginNode) -> Result<Vec<ExtMetadataBlock>> {
+        let data: &DVDynamicData = &plugin_node.dv_dynamic_data;
+
+        let mut blocks = vec![ExtMetadataBlock::Level1((&data.level1).into())];
+
+        for level2 in data.level2.iter().flatten() {
+            let target_max_pq = self.target_max_pq(level2.tid)?;
+            blocks.push(ExtMetadataBlock::Level2(
+                level2.to_block_with_target_max_pq(target_max_pq),
+            ));
+        }
+
+        if let Some(level5) = &data.level5 {
+            blocks.push(ExtMetadataBlock::Level5(
+                level5.to_block_with_canvas(self.canvas),
+            ));
+        }
+
+        // Only for CM v4.0
+        if self.cm_version == CmVersion::V40 {
+            if let Some(level3) = &data.level3 {
+                blocks.push(ExtMetadataBlock::Level3(level3.into()));
+            }
+
+            for level8 in data.level8.iter().flatten() {
+                blocks.push(ExtMetadataBlock::Level8(level8.into()));
+            }
+
+            if let Some(level9) = &data.level9 {
+                blocks.push(ExtMetadataBlock::Level9(level9.into()));
+            }
+
+            if let Some(level11) = &plugin_node.level11 {
+                blocks.push(ExtMetadataBlock::Level11(level11.into()));
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    fn target_max_pq(&self, tid: usize) -> Result<u16> {
+        if let Some(target_max_pq) = self.targets_map.get(&tid) {
+            return Ok(*target_max_pq);
+        }
+
+        match PREDEFINED_TARGET_DISPLAYS.iter().find(|d| d[0] == tid) {
+            Some(d) => Ok(Self::rpu_pq_u12_from_nits(d[2] as f32)),
+            None => bail!("Unknown target display ID {tid} in Level2 metadata."),
+        }
+    }
+
+    // Predefined targets in RPU don't need Level10 metadata
+    fn custom_target_block(&self, target: &Characteristics) -> Option<ExtMetadataBlockLevel10> {
+        let id = u8::try_from(target.id).ok()?;
+
+        if dolby_vision::rpu::extension_metadata::blocks::level10::PRESET_TARGET_DISPLAYS
+            .contains(&id)
+        {
+            return None;
+        }
+
+        let primaries = target.primaries.with_white_point(target.white_point);
+
+        let mut block = ExtMetadataBlockLevel10 {
+            length: 5,
+            target_display_index: id,
+            target_max_pq: Self::rpu_pq_u12_from_nits(target.peak_brightness as f32),
+            target_min_pq: Self::rpu_pq_u12_from_nits(target.minimum_brightness),
+            ..Default::default()
+        };
+
+        match primaries.get_predefined_index() {
+            Some(index) => block.target_primary_index = index as u8,
+            None => {
+                let p = primaries.to_rpu_u16();
+
+                block.length = 21;
+                block.target_primary_index = 255;
+                block.target_primary_red_x = p[0];
+                block.target_primary_red_y = p[1];
+                block.target_primary_green_x = p[2];
+                block.target_primary_green_y = p[3];
+                block.target_primary_blue_x = p[4];
+                block.target_primary_blue_y = p[5];
+                block.target_primary_white_x = p[6];
+                block.target_primary_white_y = p[7];
+            }
+        }
+
+        Some(block)
+    }
+
+    pub(crate) fn rpu_pq_u12_from_nits(nits: f32) -> u16 {
+        rpu_pq_u12_from_f32(l2pq(nits))
+    }
+}
+
+impl From<Profile> for GenerateProfile {
+    fn from(profile: Profile) -> Self {
+        match profile {
+            Profile::Profile5 => GenerateProfile::Profile5,
+            Profile::Profile81 => GenerateProfile::Profile81,
+            Profile::Profile84 => GenerateProfile::Profile84,
+        }
+    }
+}
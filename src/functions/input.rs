@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{ensure, Result};
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+use crate::functions::{ConvertOptions, Converter, RpuReader};
+use crate::MDFDocument;
+
+/// Metadata input of a command, XML is detected by file extension,
+/// otherwise it's parsed as RPU or HEVC.
+#[derive(Debug)]
+pub(crate) enum Input {
+    Xml(String),
+    Rpu(Vec<DoviRpu>),
+}
+
+impl Input {
+    pub fn open(path: &Path) -> Result<Self> {
+        let is_xml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+
+        if is_xml {
+            println!("Parsing XML file...");
+            Ok(Self::Xml(fs::read_to_string(path)?))
+        } else {
+            println!("Parsing RPU file...");
+            Ok(Self::Rpu(RpuReader::open(path)?.collect::<Result<_>>()?))
+        }
+    }
+
+    /// RPUs are converted with default options, with Level 5 in the canvas.
+    pub fn to_document(&self, canvas: (usize, usize)) -> Result<MDFDocument> {
+        match self {
+            Self::Xml(xml) => MDFDocument::from_xml_str(xml),
+            Self::Rpu(rpus) => {
+                let options = ConvertOptions {
+                    canvas,
+                    ..Default::default()
+                };
+
+                Converter::convert_rpus(rpus.clone(), options)
+            }
+        }
+    }
+
+    pub fn canvas(size: &[usize]) -> Result<(usize, usize)> {
+        ensure!(
+            size.len() == 2,
+            "Invalid canvas size. Use 'x' as delimiter, like 3840x2160"
+        );
+        ensure!(size[0] != 0 && size[1] != 0, "Invalid canvas size.");
+
+        Ok((size[0], size[1]))
+    }
+}
@@ -0,0 +1,407 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::{ensure, Result};
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel1;
+use serde::{Serialize, Serializer};
+
+use crate::cmv40::{DolbyLabsMDF, Track};
+use crate::commands::analyze::ReportFormat;
+use crate::commands::lint::LintArgs;
+use crate::display::{l2pq, pq2l, RPU_PQ_MAX};
+use crate::functions::input::Input;
+use crate::functions::{ConvertOptions, Converter, Level6Mode};
+use crate::metadata::WithTid;
+use crate::{rpu_pq_u12_from_f32, IntoCMV40, Level5, MDFDocument};
+
+// Levels only in CM v4.0 metadata
+const CMV40_LEVELS: &[u8] = &[3, 8, 9, 10, 11];
+
+/// Checks rules of delivery on metadata, fails if any issue is found.
+#[derive(Debug)]
+pub struct Linter {
+    min_shot_duration: Option<usize>,
+    require_targets: Vec<usize>,
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    InvalidRpu,
+    MissingLevel254,
+    Level1AboveMastering,
+    Level6AboveMastering,
+    MaxFallAboveMaxCll,
+    ShortShot,
+    MissingTrim,
+    Level5InShot,
+}
+
+#[derive(Debug, Serialize)]
+struct Issue {
+    rule: Rule,
+    // First frame of shot, or the frame with the issue
+    frame: Option<usize>,
+    message: String,
+}
+
+impl Linter {
+    pub fn lint(args: LintArgs) -> Result<()> {
+        let canvas = Input::canvas(&args.size)?;
+
+        let mut linter = Linter {
+            min_shot_duration: args.min_shot_duration,
+            require_targets: args.require_targets,
+            issues: Vec::new(),
+        };
+
+        let document = match Input::open(&args.input)? {
+            Input::Xml(xml) => {
+                let document = MDFDocument::from_xml_str(&xml)?;
+
+                if let MDFDocument::CMV40(mdf) = &document {
+                    linter.check_cmv40_document(mdf);
+                }
+
+                document
+            }
+            Input::Rpu(rpus) => {
+                linter.check_rpus(&rpus);
+
+                let options = ConvertOptions {
+                    canvas,
+                    level6: Level6Mode::FromRpu,
+                    ..Default::default()
+                };
+
+                Converter::convert_rpus(rpus, options)?
+            }
+        };
+
+        for output in &document.into_cmv40().outputs.outputs {
+            let level5 = output.level5();
+
+            for track in &output.video.tracks {
+                linter.check_track(track, &level5);
+            }
+        }
+
+        let issue_count = linter.issues.len();
+        let report = Report {
+            passed: linter.issues.is_empty(),
+            issues: linter.issues,
+        };
+
+        let report = match args.format {
+            ReportFormat::Text => report.to_string(),
+            ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        };
+
+        if let Some(output) = args.output {
+            let mut writer = BufWriter::new(File::create(output)?);
+            writeln!(writer, "{report}")?;
+            writer.flush()?;
+        } else {
+            println!("{report}");
+        }
+
+        ensure!(issue_count == 0, "Lint failed with {issue_count} issue(s).");
+
+        Ok(())
+    }
+
+    fn push(&mut self, rule: Rule, frame: Option<usize>, message: String) {
+        self.issues.push(Issue {
+            rule,
+            frame,
+            message,
+        });
+    }
+
+    /// Frames which are skipped or replaced by `convert`.
+    fn check_rpus(&mut self, rpus: &[DoviRpu]) {
+        let mut has_valid_frame = false;
+        let mut has_cmv40_levels = false;
+
+        for (frame, rpu) in rpus.iter().enumerate() {
+            let Some(vdr) = &rpu.vdr_dm_data else {
+                let message = if has_valid_frame {
+                    "RPU without metadata in the middle, replaced with previous metadata"
+                } else {
+                    "RPU without metadata at start, skipped"
+                };
+
+                self.push(Rule::InvalidRpu, Some(frame), message.to_string());
+                continue;
+            };
+
+            has_valid_frame = true;
+
+            // Reported once, as it applies to the whole RPU
+            if !has_cmv40_levels
+                && vdr.get_block(254).is_none()
+                && CMV40_LEVELS
+                    .iter()
+                    .any(|&level| vdr.level_blocks_iter(level).next().is_some())
+            {
+                has_cmv40_levels = true;
+                self.push(
+                    Rule::MissingLevel254,
+                    Some(frame),
+                    "CM v4.0 metadata without Level 254, converted as CM v2.9".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_cmv40_document(&mut self, mdf: &DolbyLabsMDF) {
+        let tracks = mdf
+            .outputs
+            .outputs
+            .iter()
+            .flat_map(|output| &output.video.tracks);
+
+        for (index, track) in tracks.enumerate() {
+            if track.plugin_node.level254.is_none() {
+                self.push(
+                    Rule::MissingLevel254,
+                    None,
+                    format!("Track {index} of CM v4.0 XML without Level 254"),
+                );
+            }
+        }
+    }
+
+    fn check_track(&mut self, track: &Track, level5: &Level5) {
+        let global_data = &track.plugin_node.dv_global_data;
+        let peak_brightness = global_data.mastering_display.peak_brightness;
+        let peak_pq = rpu_pq_u12_from_f32(l2pq(peak_brightness as f32));
+
+        if let Some(level6) = &track.level6 {
+            if level6.max_cll > peak_brightness {
+                self.push(
+                    Rule::Level6AboveMastering,
+                    None,
+                    format!(
+                        "MaxCLL {} nits above mastering display peak {peak_brightness} nits",
+                        level6.max_cll
+                    ),
+                );
+            }
+
+            if level6.max_fall > level6.max_cll {
+                self.push(
+                    Rule::MaxFallAboveMaxCll,
+                    None,
+                    format!(
+                        "MaxFALL {} nits above MaxCLL {} nits",
+                        level6.max_fall, level6.max_cll
+                    ),
+                );
+            }
+        }
+
+        for &tid in &self.require_targets.clone() {
+            if global_data
+                .target_displays
+                .iter()
+                .flatten()
+                .all(|target| target.id != tid)
+            {
+                self.push(
+                    Rule::MissingTrim,
+                    None,
+                    format!("Target display {tid} not found in track"),
+                );
+            }
+        }
+
+        for shot in track.shots.iter().flatten() {
+            let record_in = shot.record.in_;
+            let data = &shot.plugin_node.dv_dynamic_data;
+
+            let max_pq = ExtMetadataBlockLevel1::from(&data.level1).max_pq;
+            if max_pq > peak_pq {
+                self.push(
+                    Rule::Level1AboveMastering,
+                    Some(record_in),
+                    format!(
+                        "Level 1 max {:.1} nits above mastering display peak {peak_brightness} nits",
+                        pq2l(max_pq as f32 / RPU_PQ_MAX)
+                    ),
+                );
+            }
+
+            if let Some(min_shot_duration) = self.min_shot_duration {
+                if shot.record.duration < min_shot_duration {
+                    self.push(
+                        Rule::ShortShot,
+                        Some(record_in),
+                        format!(
+                            "Shot of {} frame(s), shorter than {min_shot_duration}",
+                            shot.record.duration
+                        ),
+                    );
+                }
+            }
+
+            for &tid in &self.require_targets.clone() {
+                let has_trim = data.level2.iter().flatten().any(|l| l.tid() == tid)
+                    || data.level8.iter().flatten().any(|l| l.tid() == tid);
+
+                if !has_trim {
+                    self.push(
+                        Rule::MissingTrim,
+                        Some(record_in),
+                        format!("No trim for target display {tid} in shot"),
+                    );
+                }
+            }
+
+            let shot_level5 = data.level5.as_ref().unwrap_or(level5);
+            let changed_frame = shot.frames.iter().flatten().find(|frame| {
+                frame
+                    .plugin_node
+                    .dv_dynamic_data
+                    .level5
+                    .as_ref()
+                    .is_some_and(|frame_level5| frame_level5 != shot_level5)
+            });
+
+            if let Some(frame) = changed_frame {
+                self.push(
+                    Rule::Level5InShot,
+                    Some(record_in + frame.edit_offset),
+                    format!("Level 5 changes inside shot starting at frame {record_in}"),
+                );
+            }
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::InvalidRpu => "invalid_rpu",
+            Self::MissingLevel254 => "missing_level254",
+            Self::Level1AboveMastering => "level1_above_mastering",
+            Self::Level6AboveMastering => "level6_above_mastering",
+            Self::MaxFallAboveMaxCll => "max_fall_above_max_cll",
+            Self::ShortShot => "short_shot",
+            Self::MissingTrim => "missing_trim",
+            Self::Level5InShot => "level5_in_shot",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+// Same name in text and JSON reports
+impl Serialize for Rule {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    passed: bool,
+    issues: Vec<Issue>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.issues.is_empty() {
+            return write!(f, "No issue found.");
+        }
+
+        write!(f, "Found {} issue(s):", self.issues.len())?;
+
+        for issue in &self.issues {
+            match issue.frame {
+                Some(frame) => write!(f, "\n  [{}] Frame {frame}: {}", issue.rule, issue.message)?,
+                None => write!(f, "\n  [{}] {}", issue.rule, issue.message)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dolby_vision::rpu::extension_metadata::blocks::{
+        ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel2,
+    };
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+
+    use crate::functions::{ConvertOptions, Converter};
+    use crate::IntoCMV40;
+
+    use super::{Linter, Rule};
+
+    #[test]
+    fn test_check_track() {
+        let config = GenerateConfig {
+            length: 5,
+            shots: vec![
+                VideoShot {
+                    duration: 2,
+                    metadata_blocks: vec![ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::new(
+                        0, 4095, 2048,
+                    ))],
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 3,
+                    metadata_blocks: vec![ExtMetadataBlock::Level2(
+                        ExtMetadataBlockLevel2::from_nits(100),
+                    )],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap();
+        let mdf = Converter::convert_rpus(rpus, ConvertOptions::default())
+            .unwrap()
+            .into_cmv40();
+
+        let mut linter = Linter {
+            min_shot_duration: Some(3),
+            require_targets: vec![1],
+            issues: Vec::new(),
+        };
+
+        let output = &mdf.outputs.outputs[0];
+        linter.check_track(&output.video.tracks[0], &output.level5());
+
+        let issues = linter
+            .issues
+            .iter()
+            .map(|issue| (issue.rule, issue.frame))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            issues,
+            [
+                (Rule::Level1AboveMastering, Some(0)),
+                (Rule::ShortShot, Some(0)),
+                (Rule::MissingTrim, Some(0)),
+            ]
+        );
+
+        let json = serde_json::to_string(&linter.issues[0]).unwrap();
+        assert!(json.contains(&format!("\"rule\":\"{}\"", Rule::Level1AboveMastering)));
+        assert_eq!(
+            Rule::Level1AboveMastering.to_string(),
+            "level1_above_mastering"
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::{bail, Result};
+
+use crate::cmv29::AlgorithmVersions;
+use crate::cmv40::{DVDynamicData, Track};
+use crate::commands::migrate::{MigrateArgs, XmlVersion};
+use crate::{
+    cmv29, cmv40, IntoCMV29, IntoCMV40, Level11, Level254, Level3, Level9, MDFDocument, MDFType,
+    Revision, RevisionHistory, UUIDv4, Version, XMLVersion, CMV40_MIN_VERSION,
+};
+
+#[derive(Debug, Default)]
+pub struct Migrator {
+    // Dropped field name -> count
+    dropped: BTreeMap<&'static str, usize>,
+}
+
+impl Migrator {
+    pub fn migrate(args: MigrateArgs) -> Result<()> {
+        let input = match args.input {
+            Some(input) => input,
+            None => bail!("No input file provided."),
+        };
+
+        let xml_version = XMLVersion::from(args.xml_version);
+        let version = Version::from(xml_version);
+
+        println!("Parsing XML file...");
+
+        let document = MDFDocument::from_file(input)?;
+
+        println!(
+            "Converting v{} XML to v{} XML...",
+            document.version(),
+            version
+        );
+
+        let mut migrator = Migrator::default();
+
+        let document = match (document, xml_version) {
+            (MDFDocument::CMV29(mut mdf), XMLVersion::V205) => {
+                Self::add_revision(&mut mdf.revision_history);
+                MDFDocument::CMV29(mdf)
+            }
+            (MDFDocument::CMV29(mdf), _) => {
+                let mut mdf = migrator.update_version(Self::upgrade(mdf), version);
+                Self::add_revision(&mut mdf.revision_history);
+                MDFDocument::CMV40(mdf)
+            }
+            (MDFDocument::CMV40(mut mdf), XMLVersion::V205) => {
+                Self::add_revision(&mut mdf.revision_history);
+                MDFDocument::CMV29(migrator.downgrade(mdf))
+            }
+            (MDFDocument::CMV40(mdf), _) => {
+                let mut mdf = migrator.update_version(mdf, version);
+                Self::add_revision(&mut mdf.revision_history);
+                MDFDocument::CMV40(mdf)
+            }
+        };
+
+        migrator.report(version);
+
+        let output = if let Some(output) = args.output {
+            output
+        } else {
+            println!("No output file provided, writing to metadata.xml at current path...");
+            "./metadata.xml".into()
+        };
+
+        let mut output_buffer = BufWriter::new(File::create(output)?);
+        write!(output_buffer, "{}", document.to_xml_string()?)?;
+
+        Ok(())
+    }
+
+    /// Upgrade CM v2.9 XML to CM v4.0 layout, with required metadata synthesized.
+    fn upgrade(mdf: cmv29::DolbyLabsMDF) -> cmv40::DolbyLabsMDF {
+        let sources = mdf
+            .source_list
+            .iter()
+            .flat_map(|list| list.sources.iter().flatten())
+            .map(|source| (source.unique_id.clone(), source.in_ + source.duration))
+            .collect::<Vec<_>>();
+
+        // Information lost in conversion: algorithm versions and source of each track
+        let tracks_info = mdf
+            .outputs
+            .iter()
+            .flat_map(|outputs| outputs.outputs.iter().flatten())
+            .map(|output| {
+                output
+                    .video
+                    .tracks
+                    .iter()
+                    .map(|track| {
+                        let parent_id = track
+                            .shots
+                            .iter()
+                            .flatten()
+                            .next()
+                            .map(|shot| shot.source.parent_id.clone());
+
+                        (track.plugin_node.dolby_edr.algorithm_versions, parent_id)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut mdf = mdf.into_cmv40();
+
+        for (output, tracks_info) in mdf.outputs.outputs.iter_mut().zip(tracks_info) {
+            for (track, (algorithm_versions, parent_id)) in
+                output.video.tracks.iter_mut().zip(tracks_info)
+            {
+                Self::upgrade_track(track, algorithm_versions);
+
+                if let Some(source) =
+                    parent_id.and_then(|id| sources.iter().find(|(source_id, _)| *source_id == id))
+                {
+                    Self::update_source(track, source);
+                }
+            }
+        }
+
+        mdf
+    }
+
+    /// Downgrade CM v4.0 XML to CM v2.9, with unsupported metadata dropped.
+    fn downgrade(&mut self, mdf: cmv40::DolbyLabsMDF) -> cmv29::DolbyLabsMDF {
+        let mut mdf = mdf;
+        self.strip_cmv40_metadata(&mut mdf);
+
+        let track_ids = Self::tracks_mut(&mut mdf)
+            .map(|track| track.unique_id.clone())
+            .collect::<Vec<_>>();
+
+        let mut mdf = mdf.into_cmv29();
+
+        // The track ID is the source of shots, as kept by `update_source` in upgrade
+        let tracks = mdf
+            .outputs
+            .iter_mut()
+            .flat_map(|outputs| outputs.outputs.iter_mut().flatten())
+            .flat_map(|output| output.video.tracks.iter_mut());
+
+        for (track, track_id) in tracks.zip(track_ids) {
+            for shot in track.shots.iter_mut().flatten() {
+                shot.source.parent_id = track_id.clone();
+            }
+        }
+
+        mdf.source_list = mdf
+            .outputs
+            .as_ref()
+            .and_then(|outputs| outputs.get_source_list());
+
+        mdf
+    }
+
+    fn upgrade_track(track: &mut Track, algorithm_versions: MDFType<AlgorithmVersions>) {
+        // CM v2.9 algorithm version is "2,x", and CM v4.0 is "4 x"
+        let minor = algorithm_versions
+            .into_inner()
+            .into_iter()
+            .nth(1)
+            .unwrap_or(1);
+
+        track.plugin_node.level254 = Some(Level254 {
+            cm_version: format!("4 {minor}"),
+            ..Default::default()
+        });
+
+        let mastering_display = &track.plugin_node.dv_global_data.mastering_display;
+        let level9 = Level9::from(
+            mastering_display
+                .primaries
+                .with_white_point(mastering_display.white_point),
+        );
+
+        let fill_defaults = |data: &mut DVDynamicData| {
+            data.level3.get_or_insert_with(Level3::default);
+            data.level9.get_or_insert_with(|| level9.clone());
+        };
+
+        for shot in track.shots.iter_mut().flatten() {
+            fill_defaults(&mut shot.plugin_node.dv_dynamic_data);
+
+            for frame in shot.frames.iter_mut().flatten() {
+                fill_defaults(&mut frame.plugin_node.dv_dynamic_data);
+            }
+        }
+    }
+
+    // There is no source list in CM v4.0, the source of a track is kept as the track ID.
+    fn update_source(track: &mut Track, source: &(UUIDv4, usize)) {
+        let (unique_id, source_end) = source;
+
+        track.unique_id = unique_id.clone();
+
+        let record_end = track
+            .shots
+            .iter()
+            .flatten()
+            .map(|shot| shot.record.in_ + shot.record.duration)
+            .max()
+            .unwrap_or(0);
+
+        if record_end > *source_end {
+            println!(
+                "Shots in track {} exceed the end of source ({} > {}).",
+                track.track_name, record_end, source_end
+            );
+        }
+    }
+
+    fn update_version(
+        &mut self,
+        mdf: cmv40::DolbyLabsMDF,
+        version: Version,
+    ) -> cmv40::DolbyLabsMDF {
+        let mut mdf = mdf;
+
+        mdf.xmlns = version.get_dolby_xmlns();
+        mdf.version = version;
+
+        for track in Self::tracks_mut(&mut mdf) {
+            if version > CMV40_MIN_VERSION {
+                track
+                    .plugin_node
+                    .level11
+                    .get_or_insert_with(Level11::default);
+                track.plugin_node.dv_global_data.update_application_type();
+            } else {
+                self.strip_cmv510_metadata(track);
+            }
+        }
+
+        mdf
+    }
+
+    fn strip_cmv40_metadata(&mut self, mdf: &mut cmv40::DolbyLabsMDF) {
+        for track in Self::tracks_mut(mdf) {
+            self.strip_cmv510_metadata(track);
+
+            self.take("Level254", &mut track.plugin_node.level254);
+
+            for shot in track.shots.iter_mut().flatten() {
+                self.strip_dynamic_data(&mut shot.plugin_node.dv_dynamic_data);
+
+                for frame in shot.frames.iter_mut().flatten() {
+                    self.strip_dynamic_data(&mut frame.plugin_node.dv_dynamic_data);
+                }
+            }
+        }
+    }
+
+    fn strip_cmv510_metadata(&mut self, track: &mut Track) {
+        let global_data = &mut track.plugin_node.dv_global_data;
+
+        self.take(
+            "ApplicationType",
+            &mut global_data.mastering_display.application_type,
+        );
+
+        for target in global_data.target_displays.iter_mut().flatten() {
+            self.take("ApplicationType", &mut target.application_type);
+        }
+
+        self.take("Level11", &mut track.plugin_node.level11);
+
+        for shot in track.shots.iter_mut().flatten() {
+            self.take("Level11", &mut shot.plugin_node.level11);
+
+            for frame in shot.frames.iter_mut().flatten() {
+                self.take("Level11", &mut frame.plugin_node.level11);
+            }
+        }
+    }
+
+    fn strip_dynamic_data(&mut self, data: &mut DVDynamicData) {
+        self.take("Level3", &mut data.level3);
+        self.take("Level9", &mut data.level9);
+
+        if let Some(level8) = data.level8.take() {
+            *self.dropped.entry("Level8").or_default() += level8.len();
+        }
+    }
+
+    fn take<T>(&mut self, name: &'static str, value: &mut Option<T>) {
+        if value.take().is_some() {
+            *self.dropped.entry(name).or_default() += 1;
+        }
+    }
+
+    fn tracks_mut(mdf: &mut cmv40::DolbyLabsMDF) -> impl Iterator<Item = &mut Track> {
+        mdf.outputs
+            .outputs
+            .iter_mut()
+            .flat_map(|output| output.video.tracks.iter_mut())
+    }
+
+    pub(crate) fn add_revision(revision_history: &mut Option<RevisionHistory>) {
+        match revision_history {
+            Some(RevisionHistory {
+                revisions: Some(revisions),
+            }) => revisions.push(Revision::new()),
+            _ => *revision_history = Some(RevisionHistory::new()),
+        }
+    }
+
+    fn report(&self, version: Version) {
+        if self.dropped.is_empty() {
+            return;
+        }
+
+        println!("Dropped metadata not supported by v{version} XML:");
+
+        for (name, count) in &self.dropped {
+            println!("  {name}: {count}");
+        }
+    }
+}
+
+impl From<XmlVersion> for XMLVersion {
+    fn from(version: XmlVersion) -> Self {
+        match version {
+            XmlVersion::V205 => XMLVersion::V205,
+            XmlVersion::V402 => XMLVersion::V402,
+            XmlVersion::V510 => XMLVersion::V510,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+    use dolby_vision::rpu::vdr_dm_data::CmVersion;
+
+    use super::Migrator;
+    use crate::cmv29;
+    use crate::functions::{ConvertOptions, Converter};
+    use crate::{MDFDocument, UUIDv4, Version, XMLVersion};
+
+    fn generate_cmv29() -> cmv29::DolbyLabsMDF {
+        let config = GenerateConfig {
+            cm_version: CmVersion::V29,
+            length: 5,
+            shots: vec![
+                VideoShot {
+                    duration: 2,
+                    ..Default::default()
+                },
+                VideoShot {
+                    duration: 3,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list().unwrap();
+        let MDFDocument::CMV29(mdf) =
+            Converter::convert_rpus(rpus, ConvertOptions::default()).unwrap()
+        else {
+            panic!("CM v2.9 RPU should be converted to CM v2.9 XML");
+        };
+
+        mdf
+    }
+
+    fn source_ids(mdf: &cmv29::DolbyLabsMDF) -> Vec<UUIDv4> {
+        let sources = mdf
+            .source_list
+            .iter()
+            .flat_map(|list| list.sources.iter().flatten());
+        sources.map(|source| source.unique_id.clone()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mdf = generate_cmv29();
+        let original_source_ids = source_ids(&mdf);
+        assert_eq!(original_source_ids.len(), 1);
+
+        let mut migrator = Migrator::default();
+        let mdf = Migrator::upgrade(mdf);
+        let mdf = migrator.update_version(mdf, XMLVersion::V510.into());
+        assert_eq!(mdf.version, Version::from(XMLVersion::V510));
+
+        let track = &mdf.outputs.outputs[0].video.tracks[0];
+        assert_eq!(track.unique_id, original_source_ids[0]);
+        assert!(track.plugin_node.level254.is_some());
+        assert!(track.plugin_node.level11.is_some());
+
+        let shots = track.shots.as_ref().unwrap();
+        assert_eq!(shots.len(), 2);
+        let data = &shots[0].plugin_node.dv_dynamic_data;
+        assert!(data.level3.is_some() && data.level9.is_some());
+        assert!(migrator.dropped.is_empty());
+
+        let mdf = migrator.downgrade(mdf);
+        assert_eq!(source_ids(&mdf), original_source_ids);
+
+        let shots = mdf.outputs.as_ref().unwrap().outputs.as_ref().unwrap()[0]
+            .video
+            .tracks[0]
+            .shots
+            .as_ref()
+            .unwrap();
+        assert_eq!(shots.len(), 2);
+        assert!(shots
+            .iter()
+            .all(|shot| shot.source.parent_id == original_source_ids[0]));
+
+        // Metadata synthesized in upgrade is reported as dropped
+        let dropped = migrator.dropped.into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            dropped,
+            [
+                ("ApplicationType", 1),
+                ("Level11", 1),
+                ("Level254", 1),
+                ("Level3", 2),
+                ("Level9", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_version() {
+        let mut migrator = Migrator::default();
+        let mdf = Migrator::upgrade(generate_cmv29());
+        let mdf = migrator.update_version(mdf, XMLVersion::V510.into());
+
+        let mdf = migrator.update_version(mdf, XMLVersion::V402.into());
+        assert_eq!(mdf.version, Version::from(XMLVersion::V402));
+
+        let track = &mdf.outputs.outputs[0].video.tracks[0];
+        assert!(track.plugin_node.level11.is_none());
+        assert!(track.plugin_node.level254.is_some());
+        assert_eq!(migrator.dropped.get("Level11"), Some(&1));
+    }
+}
@@ -1,4 +1,49 @@
-pub use convert::Converter;
+#[cfg(feature = "cli")]
+pub use analyze::Analyzer;
+pub use convert::{ConvertOptions, Converter, Level6Mode, Reel, ReelLayout};
+#[cfg(feature = "cli")]
+pub use csv::CsvConverter;
+pub use cut::{CutDetector, CutThresholds, Dissolve, DissolveDetector};
+#[cfg(feature = "cli")]
+pub use diff::Differ;
+pub use edit_list::{CutMismatch, EditList, EdlCuts, EdlMode};
+#[cfg(feature = "cli")]
 pub use edl::EdlConverter;
+#[cfg(feature = "cli")]
+pub use generate::Generator;
+#[cfg(feature = "cli")]
+pub use lint::Linter;
+#[cfg(feature = "cli")]
+pub use migrate::Migrator;
+pub use reader::{RpuReader, ShotOptions, ShotReader};
+pub use timecode::TimecodeFormat;
+pub use unique_id::{IdGenerator, IdKind, IdMode};
+#[cfg(feature = "cli")]
+pub use verify::Verifier;
+
+#[cfg(feature = "cli")]
+mod analyze;
 mod convert;
+#[cfg(feature = "cli")]
+mod csv;
+mod cut;
+#[cfg(feature = "cli")]
+mod diff;
+mod edit_list;
+#[cfg(feature = "cli")]
 mod edl;
+#[cfg(feature = "cli")]
+mod field;
+#[cfg(feature = "cli")]
+mod generate;
+#[cfg(feature = "cli")]
+mod input;
+#[cfg(feature = "cli")]
+mod lint;
+#[cfg(feature = "cli")]
+mod migrate;
+mod reader;
+mod timecode;
+mod unique_id;
+#[cfg(feature = "cli")]
+mod verify;
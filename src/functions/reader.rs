@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+use crate::cmv40::{Characteristics, Shot, Track};
+use crate::display;
+use crate::functions::edit_list::EdlCutMatcher;
+use crate::functions::{CutDetector, CutMismatch, CutThresholds, EdlCuts};
+use crate::levels::UHD_CANVAS;
+
+const CHUNK_SIZE: usize = 100_000;
+const NAL_START_CODE: &[u8] = &[0, 0, 1];
+const NAL_UNSPEC62: u8 = 62;
+// First byte of a bare RPU without NAL header, as extracted by dovi_tool
+const RPU_PREFIX: u8 = 0x19;
+
+/// Reads RPUs in decode order from an Annex B byte stream,
+/// which can be either a bare RPU file or a HEVC bitstream.
+pub struct RpuReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    // Position in buffer from which the next start code is searched
+    search_offset: usize,
+    // Length of bytes in buffer which are already read
+    consumed: usize,
+    eof: bool,
+    index: usize,
+}
+
+impl RpuReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(input: P) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(input)?)))
+    }
+}
+
+impl<R: Read> RpuReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            search_offset: 0,
+            consumed: 0,
+            eof: false,
+            index: 0,
+        }
+    }
+
+    fn fill_buffer(&mut self) -> Result<()> {
+        let len = self.buffer.len();
+        self.buffer.resize(len + CHUNK_SIZE, 0);
+
+        let n = self.reader.read(&mut self.buffer[len..])?;
+        self.buffer.truncate(len + n);
+
+        if n == 0 {
+            self.eof = true;
+        }
+
+        Ok(())
+    }
+
+    fn find_start_code(&self, from: usize) -> Option<usize> {
+        self.buffer
+            .get(from..)?
+            .windows(NAL_START_CODE.len())
+            .position(|w| w == NAL_START_CODE)
+            .map(|i| i + from)
+    }
+
+    /// Range of next NAL unit in buffer, without start code and trailing zero bytes.
+    /// The bytes before the range end are consumed by the next call.
+    fn next_nal(&mut self) -> Result<Option<(usize, usize)>> {
+        self.buffer.drain(..self.consumed);
+        self.consumed = 0;
+
+        // Locate the start of current NAL unit
+        let start = loop {
+            match self.find_start_code(0) {
+                Some(i) => break i + NAL_START_CODE.len(),
+                None if self.eof => return Ok(None),
+                None => {
+                    // Drop leading garbage, the start code may be split between chunks
+                    let garbage_len = self.buffer.len().saturating_sub(NAL_START_CODE.len() - 1);
+                    self.buffer.drain(..garbage_len);
+                    self.fill_buffer()?;
+                }
+            }
+        };
+
+        self.search_offset = self.search_offset.max(start);
+
+        // Locate the start of next NAL unit
+        let end = loop {
+            match self.find_start_code(self.search_offset) {
+                Some(i) => break i,
+                None if self.eof => break self.buffer.len(),
+                None => {
+                    // Start code may be split between chunks
+                    self.search_offset = self
+                        .buffer
+                        .len()
+                        .saturating_sub(NAL_START_CODE.len() - 1)
+                        .max(start);
+                    self.fill_buffer()?;
+                }
+            }
+        };
+
+        let trailing_zeros = self.buffer[start..end]
+            .iter()
+            .rev()
+            .take_while(|b| **b == 0)
+            .count();
+
+        self.consumed = end;
+        self.search_offset = 0;
+
+        Ok(Some((start, end - trailing_zeros)))
+    }
+}
+
+impl<R: Read> Iterator for RpuReader<R> {
+    type Item = Result<DoviRpu>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (start, end) = match self.next_nal() {
+                Ok(Some(range)) => range,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+
+            let nal = &self.buffer[start..end];
+
+            let is_rpu = match nal.first() {
+                Some(&RPU_PREFIX) => true,
+                Some(b) => (b >> 1) & 0x3F == NAL_UNSPEC62,
+                None => false,
+            };
+
+            if is_rpu {
+                let index = self.index;
+                self.index += 1;
+
+                return Some(
+                    DoviRpu::parse_unspec62_nalu(nal)
+                        .with_context(|| format!("Found invalid RPU: Index {index}")),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShotOptions {
+    pub canvas: (usize, usize),
+    pub skip: usize,
+    pub count: Option<usize>,
+    pub offset: usize,
+    pub keep_offset: bool,
+    pub drop_per_frame: bool,
+    // Detect cuts from metadata instead of trusting scene refresh flags, when set
+    pub cut_detection: Option<CutThresholds>,
+}
+
+impl Default for ShotOptions {
+    fn default() -> Self {
+        Self {
+            canvas: UHD_CANVAS,
+            skip: 0,
+            count: None,
+            offset: 0,
+            keep_offset: false,
+            drop_per_frame: false,
+            cut_detection: None,
+        }
+    }
+}
+
+/// Builds shots from RPUs in decode order.
+/// Each shot is returned once the next one starts, so only one shot is kept in memory.
+pub struct ShotReader<I> {
+    rpus: I,
+    options: ShotOptions,
+    frame_index: usize,
+    // Number of frames left to be parsed
+    count: usize,
+    leading_invalid_frame_count: usize,
+    invalid_frame_count: usize,
+    first_valid_frame_index: Option<usize>,
+    last_shot: Option<Shot>,
+    cut_detector: Option<CutDetector>,
+    edl_cuts: Option<EdlCutMatcher>,
+    // Global information from the first valid frame
+    track: Option<Track>,
+    targets_map: HashMap<usize, Characteristics>,
+}
+
+impl<I> ShotReader<I>
+where
+    I: Iterator<Item = Result<DoviRpu>>,
+{
+    pub fn new(rpus: I, options: ShotOptions) -> Self {
+        Self {
+            rpus,
+            options,
+            frame_index: 0,
+            count: options.count.unwrap_or(usize::MAX),
+            leading_invalid_frame_count: 0,
+            invalid_frame_count: 0,
+            first_valid_frame_index: None,
+            last_shot: None,
+            cut_detector: options.cut_detection.map(CutDetector::new),
+            edl_cuts: None,
+            track: None,
+            targets_map: HashMap::new(),
+        }
+    }
+
+    /// Use cuts from an EDL as shot boundaries, with cuts in RPU depending on the mode.
+    pub fn with_edl_cuts(mut self, cuts: EdlCuts) -> Self {
+        self.edl_cuts = Some(EdlCutMatcher::new(cuts));
+        self
+    }
+
+    /// Parse shot-based and frame-based metadata of a frame.
+    /// Returns the last shot if the frame starts a new one.
+    fn parse_frame(&mut self, rpu: DoviRpu) -> Option<Shot> {
+        let options = self.options;
+        let mut finished_shot = None;
+
+        if let Some(ref vdr) = rpu.vdr_dm_data {
+            if self.frame_index >= options.skip {
+                let frame_index = self.frame_index - options.skip + options.offset;
+                // TODO: Use real offset if first valid frame index is not 0?
+
+                let mut is_cut = match self.cut_detector {
+                    Some(ref mut detector) => detector.is_cut(vdr),
+                    None => vdr.scene_refresh_flag == 1,
+                };
+
+                if let Some(ref mut edl_cuts) = self.edl_cuts {
+                    let is_first = self.first_valid_frame_index.is_none();
+                    is_cut = edl_cuts.is_cut(frame_index, is_cut, is_first);
+                }
+
+                if self.first_valid_frame_index.is_none() || is_cut {
+                    let mut shot = Shot::with_canvas(vdr, options.canvas);
+                    shot.update_record(Some(frame_index), None);
+
+                    // FIXME: Assume input rpu file is valid,
+                    // so only use the first valid frame to get global information we need
+                    if self.first_valid_frame_index.is_none() {
+                        self.leading_invalid_frame_count =
+                            std::mem::take(&mut self.invalid_frame_count);
+                        if options.keep_offset {
+                            shot.update_record(None, Some(options.skip));
+                            self.frame_index += options.skip;
+                        }
+                        self.first_valid_frame_index = Some(frame_index);
+                        self.track = Some(Track::with_single_vdr(vdr));
+                    }
+
+                    finished_shot = self.last_shot.replace(shot);
+                } else if let Some(ref mut last_shot) = self.last_shot {
+                    last_shot.update_record(None, None);
+                    if !options.drop_per_frame {
+                        last_shot.append_metadata(&Shot::with_canvas(vdr, options.canvas));
+                    }
+                }
+
+                if let Some(d) = display::Characteristics::get_targets(vdr) {
+                    d.iter().for_each(|c| {
+                        let target = Characteristics::from(c.clone());
+                        self.targets_map.entry(target.id).or_insert(target);
+                    })
+                }
+
+                self.count -= 1;
+            }
+
+            self.frame_index += 1;
+        } else {
+            // Should not happen
+            if let Some(ref mut last_shot) = self.last_shot {
+                // Invalid RPU in the middle of sequence, use last valid frame
+                self.frame_index += 1;
+                last_shot.update_record(None, None);
+                if let Some(ref mut frames) = last_shot.frames {
+                    if let Some(frame) = frames.pop() {
+                        frames.push(frame.clone());
+                        frames.push(frame);
+                    }
+                }
+
+                self.count -= 1;
+            }
+
+            self.invalid_frame_count += 1;
+        }
+
+        finished_shot
+    }
+
+    /// Track with global information from the first valid frame.
+    pub fn track(&self) -> Option<&Track> {
+        self.track.as_ref()
+    }
+
+    pub fn take_track(&mut self) -> Option<Track> {
+        self.track.take()
+    }
+
+    /// Target displays found in all parsed frames, sorted by ID.
+    pub fn take_targets(&mut self) -> Vec<Characteristics> {
+        let mut targets = std::mem::take(&mut self.targets_map)
+            .into_values()
+            .collect::<Vec<_>>();
+        targets.sort_by_key(|c| c.id);
+
+        targets
+    }
+
+    /// Frames where cuts in RPU and EDL disagree, in order.
+    pub fn take_cut_mismatches(&mut self) -> Vec<CutMismatch> {
+        self.edl_cuts
+            .as_mut()
+            .map(EdlCutMatcher::take_mismatches)
+            .unwrap_or_default()
+    }
+
+    pub fn report(&self) {
+        if self.leading_invalid_frame_count > 0 {
+            println!(
+                "Skipped {} invalid frame(s) from start.",
+                self.leading_invalid_frame_count
+            );
+        }
+
+        if self.options.count.is_some() && self.count > 0 {
+            println!("Specified frame count exceeds the end.");
+        }
+
+        if self.invalid_frame_count > 0 {
+            println!(
+                "Skipped {} invalid frame(s) in the middle, replaced with previous metadata.",
+                self.invalid_frame_count
+            );
+        }
+    }
+}
+
+impl<I> Iterator for ShotReader<I>
+where
+    I: Iterator<Item = Result<DoviRpu>>,
+{
+    type Item = Result<Shot>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.count > 0 {
+            let rpu = match self.rpus.next() {
+                Some(Ok(rpu)) => rpu,
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            };
+
+            if let Some(shot) = self.parse_frame(rpu) {
+                return Some(Ok(shot));
+            }
+        }
+
+        // Push remained shot
+        self.last_shot.take().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use dolby_vision::rpu::dovi_rpu::DoviRpu;
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+
+    use super::RpuReader;
+
+    fn generate_rpus(length: usize) -> Vec<DoviRpu> {
+        let config = GenerateConfig {
+            length,
+            shots: vec![VideoShot {
+                duration: length,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        config.generate_rpu_list().unwrap()
+    }
+
+    #[test]
+    fn test_read_hevc_stream() {
+        let mut stream = Vec::new();
+
+        for rpu in generate_rpus(3) {
+            // AUD and slice NAL units around the RPU, with 3-byte start codes
+            stream.extend_from_slice(&[0, 0, 0, 1, 0x46, 0x01, 0x50]);
+            stream.extend_from_slice(&[0, 0, 1, 0x02, 0x01, 0xFF, 0xFF]);
+            stream.extend_from_slice(&[0, 0, 1]);
+            stream.extend_from_slice(&rpu.write_hevc_unspec62_nalu().unwrap());
+        }
+
+        let rpus = RpuReader::new(stream.as_slice())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(rpus.len(), 3);
+    }
+
+    #[test]
+    fn test_read_rpu_file() {
+        let mut stream = Vec::new();
+
+        for rpu in generate_rpus(3) {
+            stream.extend_from_slice(&[0, 0, 0, 1]);
+            // Remove 0x7C01
+            stream.extend_from_slice(&rpu.write_hevc_unspec62_nalu().unwrap()[2..]);
+        }
+
+        let rpus = RpuReader::new(stream.as_slice())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(rpus.len(), 3);
+
+        assert!(RpuReader::new([0, 0, 0, 1, 0x46, 0x01, 0x50].as_slice())
+            .next()
+            .is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::cmv40::EditRate;
+
+/// Conversion between frame counts and SMPTE timecode at a frame rate.
+///
+/// Frame rates of `NUM/1` and `NUM/1001` are supported, timecode of `NUM/1001` counts frames
+/// at the nominal integer rate. For high frame rates of 100 fps or more, frames are counted
+/// with more digits, like `01:00:00:119` for 120 fps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimecodeFormat {
+    /// Nominal frame rate.
+    pub fps: usize,
+    pub drop_frame: bool,
+}
+
+impl TimecodeFormat {
+    pub fn new(edit_rate: EditRate, drop_frame: bool) -> Result<Self> {
+        edit_rate.validate()?;
+
+        let (fps, is_ntsc) = match edit_rate.normalize().0 {
+            [n, 1] => (n, false),
+            [n, 1001] if n % 1000 == 0 => (n / 1000, true),
+            _ => bail!("Unsupported frame rate {edit_rate}, only NUM/1 or NUM/1001 is supported."),
+        };
+
+        ensure!(
+            !drop_frame || (is_ntsc && fps % 30 == 0),
+            "Drop-frame timecode is only defined for multiples of 30000/1001."
+        );
+
+        Ok(Self { fps, drop_frame })
+    }
+
+    // Frame numbers skipped at the start of each minute, 2 for 29.97 fps
+    fn dropped(&self) -> usize {
+        if self.drop_frame {
+            self.fps / 15
+        } else {
+            0
+        }
+    }
+
+    /// Timecode of a frame count.
+    /// Drop-frame timecode skips the first frame numbers of each minute, except every tenth minute.
+    pub fn timecode(&self, frame: usize) -> String {
+        let fps = self.fps;
+        let dropped = self.dropped();
+
+        let frame = if dropped > 0 {
+            let per_minute = fps * 60 - dropped;
+            let per_ten_minutes = fps * 600 - dropped * 9;
+
+            let tens = frame / per_ten_minutes;
+            let minutes = (frame % per_ten_minutes).saturating_sub(dropped) / per_minute;
+
+            frame + dropped * (9 * tens + minutes)
+        } else {
+            frame
+        };
+
+        let separator = if self.drop_frame { ';' } else { ':' };
+        let width = (fps - 1).to_string().len().max(2);
+
+        format!(
+            "{:02}:{:02}:{:02}{separator}{:0width$}",
+            frame / (fps * 3600),
+            frame / (fps * 60) % 60,
+            frame / fps % 60,
+            frame % fps
+        )
+    }
+
+    /// Frame count of a timecode `HH:MM:SS:FF`, `;` is also accepted as the separator of frames.
+    /// An integer is parsed as a frame count directly.
+    pub fn parse(&self, timecode: &str) -> Result<usize> {
+        let timecode = timecode.trim();
+
+        if let Ok(frames) = timecode.parse::<usize>() {
+            return Ok(frames);
+        }
+
+        let parts = timecode
+            .split([':', ';', '.'])
+            .map(str::parse::<usize>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|parts| parts.len() == 4)
+            .with_context(|| format!("Invalid timecode {timecode}."))?;
+
+        let [hours, minutes, seconds, frames] = [parts[0], parts[1], parts[2], parts[3]];
+        let dropped = self.dropped();
+
+        ensure!(
+            minutes < 60 && seconds < 60 && frames < self.fps,
+            "Invalid timecode {timecode}."
+        );
+        ensure!(
+            dropped == 0 || seconds > 0 || minutes % 10 == 0 || frames >= dropped,
+            "Timecode {timecode} is dropped in drop-frame timecode."
+        );
+
+        let total_minutes = hours * 60 + minutes;
+
+        Ok((total_minutes * 60 + seconds) * self.fps + frames
+            - dropped * (total_minutes - total_minutes / 10))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimecodeFormat;
+    use crate::cmv40::EditRate;
+
+    #[test]
+    fn test_timecode() {
+        let format = TimecodeFormat::new(EditRate([30000, 1001]), true).unwrap();
+        assert_eq!(format.timecode(1799), "00:00:59;29");
+        assert_eq!(format.timecode(1800), "00:01:00;02");
+        assert_eq!(format.timecode(17982), "00:10:00;00");
+        assert_eq!(format.timecode(107862), "00:59:59;00");
+        assert_eq!(format.timecode(107892), "01:00:00;00");
+        assert_eq!(format.parse("00:59:59;00").unwrap(), 107862);
+        assert_eq!(format.parse("01:00:00;00").unwrap(), 107892);
+        assert!(format.parse("00:01:00;01").is_err());
+
+        let format = TimecodeFormat::new(EditRate([60000, 1001]), true).unwrap();
+        assert_eq!(format.timecode(3600), "00:01:00;04");
+        assert_eq!(format.timecode(215784), "01:00:00;00");
+
+        let format = TimecodeFormat::new(EditRate([48000, 2000]), false).unwrap();
+        assert_eq!(format.fps, 24);
+        assert_eq!(format.timecode(86400 + 12), "01:00:00:12");
+        assert_eq!(format.parse("01:00:00:12").unwrap(), 86412);
+        assert_eq!(format.parse("120").unwrap(), 120);
+        assert!(format.parse("01:00:00:24").is_err());
+
+        let format = TimecodeFormat::new(EditRate([120000, 1001]), false).unwrap();
+        assert_eq!(format.timecode(120 * 3600 + 119), "01:00:00:119");
+        assert_eq!(format.parse("01:00:00:119").unwrap(), 120 * 3600 + 119);
+
+        assert!(TimecodeFormat::new(EditRate([24000, 1001]), true).is_err());
+        assert!(TimecodeFormat::new(EditRate([30, 1]), true).is_err());
+        assert!(TimecodeFormat::new(EditRate([24000, 1003]), false).is_err());
+        assert!(TimecodeFormat::new(EditRate([24, 0]), false).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::cmv40::EditRate;
+use crate::UUIDv4;
+
+/// Source of unique IDs in output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdMode {
+    /// Random UUID v4.
+    #[default]
+    Random,
+    /// UUID v5 derived from metadata of all shots and the position in timeline.
+    Content,
+    /// Timecode of the position in timeline,
+    /// like `00000004-0000-0000-0000-000100000012` for a shot at `01:00:00:12`.
+    Timecode,
+}
+
+/// Kind of element with an ID, which keeps IDs of different elements at the same position unique.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    Output = 1,
+    Track,
+    Source,
+    Shot,
+    Frame,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IdGenerator {
+    mode: IdMode,
+    edit_rate: EditRate,
+    namespace: Uuid,
+}
+
+impl IdGenerator {
+    pub fn new(mode: IdMode, edit_rate: EditRate) -> Self {
+        Self {
+            mode,
+            edit_rate,
+            namespace: Uuid::NAMESPACE_OID,
+        }
+    }
+
+    /// Add metadata to the namespace of content-based IDs.
+    /// IDs should only be taken after all metadata is added.
+    pub fn update<T: Serialize>(&mut self, value: &T) {
+        if self.mode != IdMode::Content {
+            return;
+        }
+
+        if let Ok(bytes) = serde_json::to_vec(value) {
+            self.namespace = Uuid::new_v5(&self.namespace, &bytes);
+        }
+    }
+
+    /// ID of an element starting at frame `index` in timeline.
+    pub fn get(&self, kind: IdKind, index: usize) -> UUIDv4 {
+        self.get_in_reel(kind, 0, index)
+    }
+
+    /// ID of an element starting at frame `index` in timeline of a reel,
+    /// for reels which are not conformed to a single timeline.
+    pub fn get_in_reel(&self, kind: IdKind, reel: usize, index: usize) -> UUIDv4 {
+        match self.mode {
+            IdMode::Random => UUIDv4::new(),
+            IdMode::Content => {
+                let name = if reel == 0 {
+                    format!("{}/{}", kind as u32, index)
+                } else {
+                    format!("{}/{}/{}", kind as u32, reel, index)
+                };
+
+                Uuid::new_v5(&self.namespace, name.as_bytes()).into()
+            }
+            IdMode::Timecode => {
+                let [n, d] = self.edit_rate.0;
+                // Nominal frame rate
+                let fps = n.div_ceil(d).max(1);
+
+                let seconds = index / fps;
+                let timecode = format!(
+                    "{:04}{:02}{:02}{:04}",
+                    seconds / 3600 % 10000,
+                    seconds / 60 % 60,
+                    seconds % 60,
+                    index % fps % 10000
+                );
+
+                // Decimal digits are kept as they are
+                let node = u128::from_str_radix(&timecode, 16).unwrap_or_default();
+
+                Uuid::from_u128((kind as u128) << 96 | (reel as u128 & 0xFFFF) << 80 | node).into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdGenerator, IdKind, IdMode};
+    use crate::cmv40::EditRate;
+
+    #[test]
+    fn test_unique_id() {
+        let ids = IdGenerator::new(IdMode::Timecode, EditRate([24000, 1001]));
+        let id = ids.get(IdKind::Shot, 3600 * 24 + 12);
+        assert_eq!(
+            serde_json::to_string(&id).unwrap(),
+            "\"00000004-0000-0000-0000-000100000012\""
+        );
+        assert_eq!(
+            serde_json::to_string(&ids.get_in_reel(IdKind::Output, 2, 0)).unwrap(),
+            "\"00000001-0002-0000-0000-000000000000\""
+        );
+
+        let mut ids = IdGenerator::new(IdMode::Content, EditRate::default());
+        let mut other = ids.clone();
+        assert_eq!(ids.get(IdKind::Shot, 0), other.get(IdKind::Shot, 0));
+        assert_ne!(ids.get(IdKind::Shot, 0), ids.get(IdKind::Frame, 0));
+
+        ids.update(&1);
+        other.update(&2);
+        assert_ne!(ids.get(IdKind::Shot, 0), other.get(IdKind::Shot, 0));
+    }
+}
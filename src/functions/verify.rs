@@ -0,0 +1,524 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+
+use anyhow::{bail, Result};
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+use dolby_vision::rpu::generate::GenerateProfile;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::commands::analyze::ReportFormat;
+use crate::commands::verify::VerifyArgs;
+use crate::functions::input::Input;
+use crate::functions::{ConvertOptions, Converter, Generator, Level6Mode};
+use crate::MDFDocument;
+
+// Levels with code values to be compared, Level 4 and Level 10 are not converted from RPU
+const LEVELS: &[u8] = &[1, 2, 3, 5, 6, 8, 9, 11, 254];
+// Levels without quantization in XML, the threshold is not applied
+const EXACT_LEVELS: &[u8] = &[6, 11, 254];
+
+/// Compares metadata blocks of the source RPU and the RPU generated from XML, frame by frame.
+#[derive(Debug)]
+pub struct Verifier {
+    threshold: u16,
+    levels: BTreeMap<u8, LevelError>,
+    flagged: Vec<FlaggedFrame>,
+}
+
+// Code values of a metadata block, with the target display for Level 2 and Level 8
+#[derive(Debug)]
+struct CodeValues {
+    target: Option<u16>,
+    values: Vec<(&'static str, i32)>,
+}
+
+impl Verifier {
+    pub fn verify(args: VerifyArgs) -> Result<()> {
+        let canvas = Input::canvas(&args.size)?;
+
+        let Input::Rpu(rpus) = Input::open(&args.input)? else {
+            bail!("Input must be a binary RPU or HEVC file.");
+        };
+
+        let mdf = match &args.xml {
+            Some(xml) => {
+                println!("Parsing XML file...");
+                MDFDocument::from_xml_str(&fs::read_to_string(xml)?)?
+            }
+            None => {
+                println!("Converting RPU to XML...");
+
+                let options = ConvertOptions {
+                    canvas,
+                    level6: Level6Mode::FromRpu,
+                    ..Default::default()
+                };
+
+                // Written and parsed again, to include the precision of XML
+                let xml = Converter::convert_rpus(rpus.clone(), options)?.to_xml_string()?;
+                MDFDocument::from_xml_str(&xml)?
+            }
+        };
+
+        let generated = Generator::generate_rpus(mdf, canvas, GenerateProfile::Profile81)?;
+
+        let mut verifier = Verifier {
+            threshold: args.threshold,
+            levels: BTreeMap::new(),
+            flagged: Vec::new(),
+        };
+
+        for (frame, (rpu, other)) in rpus.iter().zip(&generated).enumerate() {
+            verifier.compare(frame, rpu, other);
+        }
+
+        let report = Report {
+            threshold: verifier.threshold,
+            frames: FrameCounts {
+                rpu: rpus.len(),
+                xml: generated.len(),
+            },
+            levels: verifier.levels.into_values().collect(),
+            flagged_frames: verifier.flagged,
+        };
+
+        let report = match args.format {
+            ReportFormat::Text => report.to_string(),
+            ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        };
+
+        if let Some(output) = args.output {
+            let mut writer = BufWriter::new(File::create(output)?);
+            writeln!(writer, "{report}")?;
+            writer.flush()?;
+        } else {
+            println!("{report}");
+        }
+
+        Ok(())
+    }
+
+    fn compare(&mut self, frame: usize, rpu: &DoviRpu, other: &DoviRpu) {
+        let (Some(vdr), Some(other_vdr)) = (&rpu.vdr_dm_data, &other.vdr_dm_data) else {
+            return;
+        };
+
+        let mut errors = Vec::new();
+
+        for &level in LEVELS {
+            let blocks = vdr
+                .level_blocks_iter(level)
+                .filter_map(CodeValues::from_block)
+                .collect::<Vec<_>>();
+            let other_blocks = other_vdr
+                .level_blocks_iter(level)
+                .filter_map(CodeValues::from_block)
+                .collect::<Vec<_>>();
+
+            if blocks.is_empty() && other_blocks.is_empty() {
+                continue;
+            }
+
+            let level_error = self.levels.entry(level).or_insert_with(|| LevelError {
+                level,
+                max_error: 0,
+                target: None,
+                field: None,
+                frame: None,
+                missing_frames: 0,
+            });
+
+            let threshold = if EXACT_LEVELS.contains(&level) {
+                0
+            } else {
+                self.threshold as u32
+            };
+
+            let mut is_missing = false;
+
+            for target in blocks
+                .iter()
+                .chain(&other_blocks)
+                .map(|block| block.target)
+                .unique()
+            {
+                let block = blocks.iter().find(|block| block.target == target);
+                let other_block = other_blocks.iter().find(|block| block.target == target);
+
+                let (Some(block), Some(other_block)) = (block, other_block) else {
+                    is_missing = true;
+                    errors.push(FieldError {
+                        level,
+                        target,
+                        field: None,
+                        rpu: None,
+                        xml: None,
+                    });
+                    continue;
+                };
+
+                for (&(field, value), &(_, other_value)) in
+                    block.values.iter().zip(&other_block.values)
+                {
+                    let error = value.abs_diff(other_value);
+
+                    if error > level_error.max_error as u32 {
+                        level_error.max_error = error as u16;
+                        level_error.target = target;
+                        level_error.field = Some(field);
+                        level_error.frame = Some(frame);
+                    }
+
+                    if error > threshold {
+                        errors.push(FieldError {
+                            level,
+                            target,
+                            field: Some(field),
+                            rpu: Some(value),
+                            xml: Some(other_value),
+                        });
+                    }
+                }
+            }
+
+            if is_missing {
+                level_error.missing_frames += 1;
+            }
+        }
+
+        if !errors.is_empty() {
+            self.flagged.push(FlaggedFrame { frame, errors });
+        }
+    }
+}
+
+impl CodeValues {
+    fn from_block(block: &ExtMetadataBlock) -> Option<Self> {
+        let (target, values) = match block {
+            ExtMetadataBlock::Level1(b) => (
+                None,
+                vec![
+                    ("min_pq", b.min_pq as i32),
+                    ("max_pq", b.max_pq as i32),
+                    ("avg_pq", b.avg_pq as i32),
+                ],
+            ),
+            ExtMetadataBlock::Level2(b) => (
+                Some(b.target_max_pq),
+                vec![
+                    ("trim_slope", b.trim_slope as i32),
+                    ("trim_offset", b.trim_offset as i32),
+                    ("trim_power", b.trim_power as i32),
+                    ("trim_chroma_weight", b.trim_chroma_weight as i32),
+                    ("trim_saturation_gain", b.trim_saturation_gain as i32),
+                    ("ms_weight", b.ms_weight as i32),
+                ],
+            ),
+            ExtMetadataBlock::Level3(b) => (
+                None,
+                vec![
+                    ("min_pq_offset", b.min_pq_offset as i32),
+                    ("max_pq_offset", b.max_pq_offset as i32),
+                    ("avg_pq_offset", b.avg_pq_offset as i32),
+                ],
+            ),
+            ExtMetadataBlock::Level5(b) => (
+                None,
+                vec![
+                    ("active_area_left_offset", b.active_area_left_offset as i32),
+                    (
+                        "active_area_right_offset",
+                        b.active_area_right_offset as i32,
+                    ),
+                    ("active_area_top_offset", b.active_area_top_offset as i32),
+                    (
+                        "active_area_bottom_offset",
+                        b.active_area_bottom_offset as i32,
+                    ),
+                ],
+            ),
+            ExtMetadataBlock::Level6(b) => (
+                None,
+                vec![
+                    (
+                        "max_display_mastering_luminance",
+                        b.max_display_mastering_luminance as i32,
+                    ),
+                    (
+                        "min_display_mastering_luminance",
+                        b.min_display_mastering_luminance as i32,
+                    ),
+                    ("max_content_light_level", b.max_content_light_level as i32),
+                    (
+                        "max_frame_average_light_level",
+                        b.max_frame_average_light_level as i32,
+                    ),
+                ],
+            ),
+            ExtMetadataBlock::Level8(b) => (
+                Some(b.target_display_index as u16),
+                vec![
+                    ("trim_slope", b.trim_slope as i32),
+                    ("trim_offset", b.trim_offset as i32),
+                    ("trim_power", b.trim_power as i32),
+                    ("trim_chroma_weight", b.trim_chroma_weight as i32),
+                    ("trim_saturation_gain", b.trim_saturation_gain as i32),
+                    ("ms_weight", b.ms_weight as i32),
+                    ("target_mid_contrast", b.target_mid_contrast as i32),
+                    ("clip_trim", b.clip_trim as i32),
+                    (
+                        "saturation_vector_field0",
+                        b.saturation_vector_field0 as i32,
+                    ),
+                    (
+                        "saturation_vector_field1",
+                        b.saturation_vector_field1 as i32,
+                    ),
+                    (
+                        "saturation_vector_field2",
+                        b.saturation_vector_field2 as i32,
+                    ),
+                    (
+                        "saturation_vector_field3",
+                        b.saturation_vector_field3 as i32,
+                    ),
+                    (
+                        "saturation_vector_field4",
+                        b.saturation_vector_field4 as i32,
+                    ),
+                    (
+                        "saturation_vector_field5",
+                        b.saturation_vector_field5 as i32,
+                    ),
+                    ("hue_vector_field0", b.hue_vector_field0 as i32),
+                    ("hue_vector_field1", b.hue_vector_field1 as i32),
+                    ("hue_vector_field2", b.hue_vector_field2 as i32),
+                    ("hue_vector_field3", b.hue_vector_field3 as i32),
+                    ("hue_vector_field4", b.hue_vector_field4 as i32),
+                    ("hue_vector_field5", b.hue_vector_field5 as i32),
+                ],
+            ),
+            ExtMetadataBlock::Level9(b) => (
+                None,
+                vec![
+                    ("source_primary_index", b.source_primary_index as i32),
+                    ("source_primary_red_x", b.source_primary_red_x as i32),
+                    ("source_primary_red_y", b.source_primary_red_y as i32),
+                    ("source_primary_green_x", b.source_primary_green_x as i32),
+                    ("source_primary_green_y", b.source_primary_green_y as i32),
+                    ("source_primary_blue_x", b.source_primary_blue_x as i32),
+                    ("source_primary_blue_y", b.source_primary_blue_y as i32),
+                    ("source_primary_white_x", b.source_primary_white_x as i32),
+                    ("source_primary_white_y", b.source_primary_white_y as i32),
+                ],
+            ),
+            ExtMetadataBlock::Level11(b) => (
+                None,
+                vec![
+                    ("content_type", b.content_type as i32),
+                    ("whitepoint", b.whitepoint as i32),
+                    ("reference_mode_flag", b.reference_mode_flag as i32),
+                    ("reserved_byte3", b.reserved_byte3 as i32),
+                ],
+            ),
+            ExtMetadataBlock::Level254(b) => (
+                None,
+                vec![
+                    ("dm_mode", b.dm_mode as i32),
+                    ("dm_version_index", b.dm_version_index as i32),
+                ],
+            ),
+            _ => return None,
+        };
+
+        Some(Self { target, values })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    threshold: u16,
+    frames: FrameCounts,
+    levels: Vec<LevelError>,
+    flagged_frames: Vec<FlaggedFrame>,
+}
+
+#[derive(Debug, Serialize)]
+struct FrameCounts {
+    rpu: usize,
+    xml: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct LevelError {
+    level: u8,
+    max_error: u16,
+    // Field and frame with the maximum error
+    target: Option<u16>,
+    field: Option<&'static str>,
+    frame: Option<usize>,
+    // Number of frames with a block missing in one of the RPUs
+    missing_frames: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct FlaggedFrame {
+    frame: usize,
+    errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize)]
+struct FieldError {
+    level: u8,
+    target: Option<u16>,
+    // None if the block is missing in one of the RPUs
+    field: Option<&'static str>,
+    rpu: Option<i32>,
+    xml: Option<i32>,
+}
+
+fn describe_field(level: u8, target: Option<u16>, field: Option<&str>) -> String {
+    let mut name = format!("L{level}");
+
+    if let Some(target) = target {
+        name.push_str(&format!("[{target}]"));
+    }
+
+    match field {
+        Some(field) => format!("{name} {field}"),
+        None => name,
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Frames: {} in RPU, {} generated from XML",
+            self.frames.rpu, self.frames.xml
+        )?;
+
+        writeln!(f, "Maximum error of code values:")?;
+
+        for level in &self.levels {
+            write!(f, "  Level {}: {}", level.level, level.max_error)?;
+
+            if let (Some(field), Some(frame)) = (level.field, level.frame) {
+                write!(
+                    f,
+                    " in {} at frame {frame}",
+                    describe_field(level.level, level.target, Some(field))
+                )?;
+            }
+
+            if level.missing_frames > 0 {
+                write!(f, ", missing in {} frame(s)", level.missing_frames)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        write!(
+            f,
+            "Frames over threshold {}: {}",
+            self.threshold,
+            self.flagged_frames.len()
+        )?;
+
+        let flagged_frames = self.flagged_frames.iter().map(|frame| {
+            let errors = frame
+                .errors
+                .iter()
+                .map(|error| {
+                    let name = describe_field(error.level, error.target, error.field);
+
+                    match (error.rpu, error.xml) {
+                        (Some(rpu), Some(xml)) => format!("{name} {rpu} -> {xml}"),
+                        _ => format!("{name} missing"),
+                    }
+                })
+                .join(", ");
+
+            (frame.frame, frame.frame, errors)
+        });
+
+        // Consecutive frames with the same errors are merged
+        let flagged_frames = flagged_frames.coalesce(|a, b| {
+            if a.1 + 1 == b.0 && a.2 == b.2 {
+                Ok((a.0, b.1, a.2))
+            } else {
+                Err((a, b))
+            }
+        });
+
+        for (first, last, errors) in flagged_frames {
+            if first == last {
+                write!(f, "\n  Frame {first}: {errors}")?;
+            } else {
+                write!(f, "\n  Frames {first}-{last}: {errors}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use dolby_vision::rpu::extension_metadata::blocks::{
+        ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel11,
+    };
+    use dolby_vision::rpu::generate::{GenerateConfig, VideoShot};
+
+    use super::Verifier;
+
+    #[test]
+    fn test_compare() {
+        let config = GenerateConfig {
+            length: 1,
+            shots: vec![VideoShot {
+                duration: 1,
+                metadata_blocks: vec![ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::new(
+                    0, 2048, 1024,
+                ))],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let rpu = config.generate_rpu_list().unwrap().remove(0);
+
+        let mut other = rpu.clone();
+        let vdr = other.vdr_dm_data.as_mut().unwrap();
+        vdr.replace_metadata_block(ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::new(
+            0, 2081, 1024,
+        )))
+        .unwrap();
+        vdr.replace_metadata_block(ExtMetadataBlock::Level11(ExtMetadataBlockLevel11::default()))
+            .unwrap();
+
+        let mut verifier = Verifier {
+            threshold: 1,
+            levels: BTreeMap::new(),
+            flagged: Vec::new(),
+        };
+
+        verifier.compare(0, &rpu, &rpu);
+        assert!(verifier.flagged.is_empty());
+        assert!(verifier.levels.values().all(|l| l.max_error == 0));
+
+        verifier.compare(1, &rpu, &other);
+        let level1 = &verifier.levels[&1];
+        assert_eq!((level1.field, level1.frame), (Some("max_pq"), Some(1)));
+
+        // Error of 1 in Level 11 is not accepted
+        let errors = &verifier.flagged[0].errors;
+        assert!(errors.iter().any(|e| e.level == 11 && e.field.is_some()));
+        assert_eq!(verifier.flagged[0].frame, 1);
+    }
+}
@@ -0,0 +1,30 @@
+//! Library for creating Dolby Vision XML metadata (DolbyLabsMDF) from binary metadata.
+//!
+//! RPUs are converted in decode order, either from [`RpuReader`] or any other source:
+//!
+//! ```no_run
+//! use dovi_meta::{ConvertOptions, Converter, RpuReader};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let rpus = RpuReader::open("RPU.bin")?.collect::<anyhow::Result<Vec<_>>>()?;
+//! let document = Converter::convert_rpus(rpus, ConvertOptions::default())?;
+//!
+//! println!("{}", document.to_xml_string()?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The command line interface is built with the `cli` feature, which is enabled by default.
+
+#[cfg(feature = "cli")]
+pub mod commands;
+pub mod functions;
+pub mod metadata;
+
+pub use functions::{
+    ConvertOptions, Converter, CutDetector, CutMismatch, CutThresholds, Dissolve, DissolveDetector,
+    EditList, EdlCuts, EdlMode, IdMode, Level6Mode, Reel, ReelLayout, RpuReader, ShotOptions,
+    ShotReader, TimecodeFormat,
+};
+pub use metadata::levels::*;
+pub use metadata::*;
@@ -1,15 +1,16 @@
-use anyhow::Result;
-use clap::Parser;
+use std::path::PathBuf;
 
-use crate::commands::Command;
-use crate::functions::{Converter, EdlConverter};
-use crate::levels::*;
-use crate::metadata::*;
-use crate::Command::{Convert, Edl};
+use anyhow::Result;
+use clap::{Parser, ValueHint};
 
-mod commands;
-mod functions;
-mod metadata;
+use dovi_meta::commands::Command;
+use dovi_meta::commands::Command::{
+    Analyze, Convert, Diff, Edl, ExportCsv, Generate, ImportCsv, Lint, Migrate, Verify,
+};
+use dovi_meta::display::Catalog;
+use dovi_meta::functions::{
+    Analyzer, Converter, CsvConverter, Differ, EdlConverter, Generator, Linter, Migrator, Verifier,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -19,6 +20,14 @@ mod metadata;
     version = option_env!("VERGEN_GIT_DESCRIBE").unwrap_or(env!("CARGO_PKG_VERSION"))
 )]
 struct Opt {
+    #[clap(
+        long,
+        global = true,
+        help = "Set the TOML or JSON catalog of displays to extend predefined ones",
+        value_hint = ValueHint::FilePath
+    )]
+    catalog: Option<PathBuf>,
+
     #[clap(subcommand)]
     cmd: Command,
 }
@@ -26,8 +35,20 @@ struct Opt {
 fn main() -> Result<()> {
     let opt = Opt::parse();
 
+    if let Some(catalog) = opt.catalog {
+        Catalog::from_file(catalog)?.install()?;
+    }
+
     match opt.cmd {
-        Convert(args) => Converter::convert(args),
+        Convert(args) => Converter::convert(*args),
         Edl(args) => EdlConverter::convert(args),
+        Generate(args) => Generator::generate(args),
+        Migrate(args) => Migrator::migrate(args),
+        Analyze(args) => Analyzer::analyze(args),
+        ExportCsv(args) => CsvConverter::export(args),
+        ImportCsv(args) => CsvConverter::import(args),
+        Diff(args) => Differ::diff(args),
+        Verify(args) => Verifier::verify(args),
+        Lint(args) => Linter::lint(args),
     }
 }
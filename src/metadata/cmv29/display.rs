@@ -1,9 +1,9 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::display::Chromaticity;
-use crate::{ColorSpace, Encoding, MDFType, Primaries, SignalRange};
+use crate::{cmv40, ColorSpace, Encoding, IntoCMV40, MDFType, Primaries, SignalRange};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Characteristics {
     // 0
     #[serde(rename = "@level")]
@@ -12,10 +12,11 @@ pub struct Characteristics {
     pub mastering_display: CharacteristicsLegacy,
     #[serde(rename = "TargetDisplay")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub target_displays: Option<Vec<CharacteristicsLegacy>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CharacteristicsLegacy {
     // 0
     #[serde(rename = "@level")]
@@ -43,3 +44,19 @@ pub struct CharacteristicsLegacy {
     #[serde(rename = "SignalRange")]
     pub signal_range: SignalRange,
 }
+
+impl IntoCMV40<cmv40::Characteristics> for CharacteristicsLegacy {
+    fn into_cmv40(self) -> cmv40::Characteristics {
+        cmv40::Characteristics {
+            id: self.id,
+            name: self.name,
+            primaries: self.primaries.into_cmv40(),
+            white_point: self.white_point.into_cmv40(),
+            peak_brightness: self.peak_brightness,
+            minimum_brightness: self.minimum_brightness,
+            eotf: self.encoding,
+            diagonal_size: self.diagonal_size,
+            application_type: None,
+        }
+    }
+}
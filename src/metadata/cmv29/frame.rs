@@ -1,9 +1,9 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cmv29::ShotPluginNode;
-use crate::UUIDv4;
+use crate::{cmv40, IntoCMV40, UUIDv4};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Frame {
     #[serde(rename = "UniqueID")]
     pub unique_id: UUIDv4,
@@ -12,3 +12,15 @@ pub struct Frame {
     #[serde(rename = "PluginNode")]
     pub plugin_node: ShotPluginNode,
 }
+
+impl IntoCMV40<cmv40::Frame> for Frame {
+    fn into_cmv40(self) -> cmv40::Frame {
+        cmv40::Frame {
+            edit_offset: self.edit_offset,
+            plugin_node: cmv40::ShotPluginNode {
+                dv_dynamic_data: self.plugin_node.into(),
+                level11: None,
+            },
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::array;
 
 pub use display::*;
@@ -6,41 +6,63 @@ pub use frame::*;
 pub use shot::*;
 pub use track::*;
 
-use crate::{RevisionHistory, UUIDv4, Version};
+use crate::XMLVersion::V402;
+use crate::{cmv40, IntoCMV40, RevisionHistory, UUIDv4, Version};
 
 mod display;
 mod frame;
 mod shot;
 mod track;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DolbyLabsMDF {
     #[serde(rename = "@version")]
     pub version: Version,
     #[serde(rename = "@xmlns:xsd")]
+    #[serde(default)]
     pub xmlns_xsd: String,
     #[serde(rename = "@xmlns:xsi")]
+    #[serde(default)]
     pub xmlns_xsi: String,
     #[serde(rename = "SourceList")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub source_list: Option<SourceList>,
     #[serde(rename = "RevisionHistory")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub revision_history: Option<RevisionHistory>,
     #[serde(rename = "Outputs")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub outputs: Option<Outputs>,
 }
 
-#[derive(Debug, Serialize)]
+impl IntoCMV40<cmv40::DolbyLabsMDF> for DolbyLabsMDF {
+    fn into_cmv40(self) -> cmv40::DolbyLabsMDF {
+        let version: Version = V402.into();
+
+        cmv40::DolbyLabsMDF {
+            xmlns: version.get_dolby_xmlns(),
+            version,
+            revision_history: self.revision_history,
+            outputs: self.outputs.into_cmv40().unwrap_or(cmv40::Outputs {
+                outputs: Vec::new(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SourceList {
     #[serde(rename = "Source")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub sources: Option<Vec<Source>>,
 }
 
 // TODO: Some other fields are available here
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Source {
     #[serde(rename = "@type")]
     pub type_: String,
@@ -52,54 +74,70 @@ pub struct Source {
     pub duration: usize,
 }
 
-#[derive(Debug, Serialize)]
+impl Source {
+    pub fn with_range(unique_id: UUIDv4, in_: usize, duration: usize) -> Self {
+        Self {
+            type_: "Video".to_string(),
+            unique_id,
+            in_,
+            duration,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Outputs {
     #[serde(rename = "Output")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub outputs: Option<Vec<Output>>,
 }
 
+impl IntoCMV40<cmv40::Outputs> for Outputs {
+    fn into_cmv40(self) -> cmv40::Outputs {
+        cmv40::Outputs {
+            outputs: self.outputs.into_cmv40().unwrap_or_default(),
+        }
+    }
+}
+
 impl Outputs {
+    /// A source for each parent ID of shots in all tracks, covering all shots of it.
     pub fn get_source_list(&self) -> Option<SourceList> {
-        if let Some(sources) = self.outputs.as_ref().map(|outputs| {
-            outputs
-                .iter()
-                .filter_map(|output| {
-                    output
-                        .video
-                        .tracks
-                        .last()
-                        .and_then(|track| track.shots.as_ref())
-                        .and_then(|shots| shots.last())
-                        .map(|shot| {
-                            let record = shot.record.clone();
-                            let source = shot.source.clone();
-
-                            let duration = record.in_ + record.duration;
-                            let unique_id = source.parent_id;
-
-                            Source {
-                                type_: "Video".to_string(),
-                                unique_id,
-                                in_: 0,
-                                duration,
-                            }
-                        })
-                })
-                .collect::<Vec<_>>()
-        }) {
-            let source_list = SourceList {
-                sources: Some(sources),
-            };
-
-            Some(source_list)
-        } else {
-            None
-        }
+        let outputs = self.outputs.as_ref()?;
+
+        // Parent ID -> (first frame, end frame)
+        let mut ranges: Vec<(UUIDv4, usize, usize)> = Vec::new();
+
+        outputs
+            .iter()
+            .flat_map(|output| output.video.tracks.iter())
+            .flat_map(|track| track.shots.iter().flatten())
+            .for_each(|shot| {
+                let source = &shot.source;
+                let end = source.in_ + shot.record.duration;
+
+                match ranges.iter_mut().find(|(id, _, _)| *id == source.parent_id) {
+                    Some((_, in_, end_)) => {
+                        *in_ = (*in_).min(source.in_);
+                        *end_ = (*end_).max(end);
+                    }
+                    None => ranges.push((source.parent_id.clone(), source.in_, end)),
+                }
+            });
+
+        let sources = ranges
+            .into_iter()
+            .map(|(unique_id, in_, end)| Source::with_range(unique_id, in_, end - in_))
+            .collect();
+
+        Some(SourceList {
+            sources: Some(sources),
+        })
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Output {
     #[serde(rename = "@name")]
     pub name: String,
@@ -117,12 +155,33 @@ pub struct Output {
     pub video: Video,
 }
 
-#[derive(Debug, Serialize)]
+impl IntoCMV40<cmv40::Output> for Output {
+    fn into_cmv40(self) -> cmv40::Output {
+        cmv40::Output {
+            composition_name: self.name,
+            unique_id: self.unique_id,
+            number_video_tracks: self.number_video_tracks,
+            canvas_aspect_ratio: self.canvas_aspect_ratio,
+            image_aspect_ratio: self.image_aspect_ratio,
+            video: self.video.into_cmv40(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Video {
     #[serde(rename = "Track")]
     pub tracks: Vec<Track>,
 }
 
+impl IntoCMV40<cmv40::Video> for Video {
+    fn into_cmv40(self) -> cmv40::Video {
+        cmv40::Video {
+            tracks: self.tracks.into_cmv40(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct AlgorithmVersions([usize; 2]);
 
@@ -132,6 +191,14 @@ impl Default for AlgorithmVersions {
     }
 }
 
+impl TryFrom<Vec<usize>> for AlgorithmVersions {
+    type Error = Vec<usize>;
+
+    fn try_from(vec: Vec<usize>) -> Result<Self, Self::Error> {
+        Ok(Self(vec.try_into()?))
+    }
+}
+
 impl IntoIterator for AlgorithmVersions {
     type Item = usize;
     type IntoIter = array::IntoIter<Self::Item, 2>;
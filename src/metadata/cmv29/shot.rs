@@ -1,10 +1,14 @@
-use serde::Serialize;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::cmv29::{Frame, Source};
 use crate::cmv40::DVDynamicData;
-use crate::{cmv40, IntoCMV29, Level1, Level2, Level5, UUIDv4};
+use crate::{
+    cmv40, AspectRatio, ImageCharacter, IntoCMV29, IntoCMV40, Level1, Level2, Level5, MDFType,
+    UUIDv4,
+};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Shot {
     #[serde(rename = "UniqueID")]
     pub unique_id: UUIDv4,
@@ -16,11 +20,26 @@ pub struct Shot {
     pub plugin_node: ShotPluginNode,
     #[serde(rename = "Frame")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub frames: Option<Vec<Frame>>,
 }
 
+impl IntoCMV40<cmv40::Shot> for Shot {
+    fn into_cmv40(self) -> cmv40::Shot {
+        cmv40::Shot {
+            unique_id: self.unique_id,
+            record: self.record.into(),
+            plugin_node: cmv40::ShotPluginNode {
+                dv_dynamic_data: self.plugin_node.into(),
+                level11: None,
+            },
+            frames: self.frames.into_cmv40(),
+        }
+    }
+}
+
 // CMv2.9 only
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ShotSource {
     #[serde(rename = "ParentID")]
     pub parent_id: UUIDv4,
@@ -37,7 +56,7 @@ impl From<Source> for ShotSource {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Record {
     #[serde(rename = "In")]
     pub in_: usize,
@@ -54,7 +73,17 @@ impl From<cmv40::Record> for Record {
     }
 }
 
-#[derive(Debug, Serialize)]
+impl From<Record> for cmv40::Record {
+    fn from(record: Record) -> Self {
+        Self {
+            in_: record.in_,
+            duration: record.duration,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "DolbyEDRList")]
 pub struct ShotPluginNode {
     #[serde(rename = "DolbyEDR")]
     pub level1: Level1,
@@ -75,3 +104,98 @@ impl From<DVDynamicData> for ShotPluginNode {
         }
     }
 }
+
+impl From<ShotPluginNode> for DVDynamicData {
+    fn from(node: ShotPluginNode) -> Self {
+        Self {
+            level1: node.level1.into_cmv40(),
+            level2: node.level2.into_cmv40(),
+            level5: node.level5.into_cmv40(),
+            ..Default::default()
+        }
+    }
+}
+
+// All levels share the same element name, distinguished by the level attribute.
+#[derive(Deserialize)]
+struct DolbyEDRList {
+    #[serde(rename = "DolbyEDR")]
+    #[serde(default)]
+    blocks: Vec<DolbyEDR>,
+}
+
+#[derive(Deserialize)]
+struct DolbyEDR {
+    #[serde(rename = "@level")]
+    level: u8,
+    #[serde(rename = "ImageCharacter")]
+    image_character: Option<MDFType<ImageCharacter>>,
+    #[serde(rename = "TID")]
+    tid: Option<usize>,
+    #[serde(rename = "Trim")]
+    trim: Option<MDFType<[f32; 9]>>,
+    #[serde(rename = "AspectRatios")]
+    aspect_ratio: Option<MDFType<AspectRatio>>,
+}
+
+impl TryFrom<DolbyEDRList> for ShotPluginNode {
+    type Error = anyhow::Error;
+
+    fn try_from(list: DolbyEDRList) -> Result<Self> {
+        let mut level1 = None;
+        let mut level2 = Vec::new();
+        let mut level5 = None;
+
+        for block in list.blocks {
+            match (block.level, block) {
+                (
+                    1,
+                    DolbyEDR {
+                        image_character: Some(image_character),
+                        ..
+                    },
+                ) => {
+                    level1 = Some(Level1 {
+                        level: 1,
+                        image_character,
+                    })
+                }
+                (
+                    2,
+                    DolbyEDR {
+                        tid: Some(tid),
+                        trim: Some(trim),
+                        ..
+                    },
+                ) => level2.push(Level2::with_padded_trim(tid, trim)),
+                (
+                    5,
+                    DolbyEDR {
+                        aspect_ratio: Some(aspect_ratio),
+                        ..
+                    },
+                ) => {
+                    level5 = Some(Level5 {
+                        level: 5,
+                        aspect_ratio,
+                    })
+                }
+                (level, _) => bail!("Invalid DolbyEDR metadata for level {level}."),
+            }
+        }
+
+        let Some(level1) = level1 else {
+            bail!("No level 1 metadata in DolbyEDR.");
+        };
+
+        Ok(Self {
+            level1,
+            level2: if level2.is_empty() {
+                None
+            } else {
+                Some(level2)
+            },
+            level5,
+        })
+    }
+}
@@ -1,14 +1,15 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cmv29::{AlgorithmVersions, Characteristics, Shot};
+use crate::cmv40::EditRate;
 use crate::display::Chromaticity;
-use crate::MDFType::CMV29;
+use crate::MDFType::{CMV29, CMV40};
 use crate::{
-    cmv40, ColorSpace, Encoding, IntoCMV29, Level6, MDFType, Primaries, SignalRange,
-    SignalRangeEnum, UUIDv4,
+    cmv40, ChromaFormat, ChromaFormatEnum, ColorSpace, Encoding, IntoCMV29, IntoCMV40, Level6,
+    MDFType, Primaries, SignalRange, UUIDv4,
 };
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Track {
     #[serde(rename = "@name")]
     pub name: String,
@@ -20,15 +21,31 @@ pub struct Track {
     pub color_encoding: ColorEncoding,
     #[serde(rename = "Level6")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level6: Option<Level6>,
     #[serde(rename = "PluginNode")]
     pub plugin_node: TrackPluginNode,
     #[serde(rename = "Shot")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub shots: Option<Vec<Shot>>,
 }
 
-#[derive(Debug, Serialize)]
+impl IntoCMV40<cmv40::Track> for Track {
+    fn into_cmv40(self) -> cmv40::Track {
+        cmv40::Track {
+            track_name: self.name,
+            unique_id: self.unique_id,
+            edit_rate: CMV40(EditRate([self.rate.n, self.rate.d])),
+            color_encoding: self.color_encoding.into(),
+            level6: self.level6,
+            plugin_node: self.plugin_node.into(),
+            shots: self.shots.into_cmv40(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Rate {
     #[serde(rename = "n")]
     pub n: usize,
@@ -36,7 +53,7 @@ pub struct Rate {
     pub d: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ColorEncoding {
     #[serde(rename = "Primaries")]
     pub primaries: Primaries,
@@ -53,9 +70,8 @@ pub struct ColorEncoding {
     pub bit_depth: usize,
     #[serde(rename = "ColorSpace")]
     pub color_space: ColorSpace,
-    // FIXME: use usize?
     #[serde(rename = "ChromaFormat")]
-    pub chroma_format: String,
+    pub chroma_format: ChromaFormat,
     #[serde(rename = "SignalRange")]
     pub signal_range: SignalRange,
 }
@@ -68,18 +84,38 @@ impl From<cmv40::ColorEncoding> for ColorEncoding {
             peak_brightness: c.peak_brightness,
             minimum_brightness: c.minimum_brightness,
             encoding: c.encoding,
-            // TODO: as an option?
-            bit_depth: 16,
+            bit_depth: c
+                .bit_depth
+                .unwrap_or(cmv40::ColorEncoding::DEFAULT_BIT_DEPTH),
             color_space: c.color_space,
-            chroma_format: "444".to_string(),
-            signal_range: SignalRange {
-                signal_range: SignalRangeEnum::Computer,
-            },
+            chroma_format: c.chroma_format.unwrap_or(ChromaFormat {
+                chroma_format: ChromaFormatEnum::default(),
+            }),
+            signal_range: c.signal_range,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+impl From<ColorEncoding> for cmv40::ColorEncoding {
+    fn from(c: ColorEncoding) -> Self {
+        Self {
+            primaries: c.primaries.into_cmv40(),
+            white_point: c.white_point.into_cmv40(),
+            peak_brightness: c.peak_brightness,
+            minimum_brightness: c.minimum_brightness,
+            encoding: c.encoding,
+            // Defaults are omitted, as they are not in CM v4.0 XML by default
+            bit_depth: Some(c.bit_depth)
+                .filter(|&bit_depth| bit_depth != cmv40::ColorEncoding::DEFAULT_BIT_DEPTH),
+            color_space: c.color_space,
+            chroma_format: Some(c.chroma_format)
+                .filter(|c| c.chroma_format != ChromaFormatEnum::default()),
+            signal_range: c.signal_range,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TrackPluginNode {
     #[serde(rename = "DolbyEDR")]
     pub dolby_edr: TrackDolbyEDR,
@@ -100,7 +136,23 @@ impl From<cmv40::TrackPluginNode> for TrackPluginNode {
     }
 }
 
-#[derive(Debug, Serialize)]
+impl From<TrackPluginNode> for cmv40::TrackPluginNode {
+    fn from(t: TrackPluginNode) -> Self {
+        let characteristics = t.dolby_edr.characteristics;
+
+        Self {
+            dv_global_data: cmv40::DVGlobalData {
+                level: 0,
+                mastering_display: characteristics.mastering_display.into_cmv40(),
+                target_displays: characteristics.target_displays.into_cmv40(),
+            },
+            level11: None,
+            level254: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TrackDolbyEDR {
     // Format: usize,usize
     #[serde(rename = "AlgorithmVersions")]
@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cmv29::CharacteristicsLegacy;
 use crate::display::Chromaticity;
@@ -8,7 +8,7 @@ use crate::{
     SignalRange, SignalRangeEnum,
 };
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Characteristics {
     #[serde(rename = "ID")]
     pub id: usize,
@@ -29,6 +29,7 @@ pub struct Characteristics {
     // Version 5.0.0+
     #[serde(rename = "ApplicationType")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub application_type: Option<ApplicationType>,
 }
 
@@ -1,10 +1,10 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cmv40::Shot;
 use crate::metadata::cmv40::ShotPluginNode;
 use crate::{cmv29, IntoCMV29, UUIDv4};
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Frame {
     #[serde(rename = "EditOffset")]
     pub edit_offset: usize,
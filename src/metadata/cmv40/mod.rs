@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub use display::*;
 pub use frame::*;
@@ -8,8 +8,8 @@ pub use track::*;
 
 use crate::XMLVersion::{V402, V510};
 use crate::{
-    cmv29, ApplicationType, ApplicationTypeEnum, IntoCMV29, Level5, RevisionHistory, UUIDv4,
-    Version, XMLVersion, CMV40_MIN_VERSION, UHD_AR,
+    cmv29, AspectRatio, IntoCMV29, Level5, RevisionHistory, UUIDv4, Version, XMLVersion,
+    CMV40_MIN_VERSION, UHD_AR,
 };
 
 mod display;
@@ -17,13 +17,16 @@ mod frame;
 mod shot;
 mod track;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DolbyLabsMDF {
+    #[serde(alias = "@xmlns")]
+    #[serde(default)]
     pub xmlns: String,
     #[serde(rename = "Version")]
     pub version: Version,
     #[serde(rename = "RevisionHistory")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub revision_history: Option<RevisionHistory>,
     #[serde(rename = "Outputs")]
     pub outputs: Outputs,
@@ -31,12 +34,16 @@ pub struct DolbyLabsMDF {
 
 impl DolbyLabsMDF {
     pub fn with_single_output(output: Output) -> Result<Self> {
-        let mut output = output;
+        Self::with_outputs(vec![output])
+    }
+
+    /// The version is determined by the first track of the first output.
+    pub fn with_outputs(outputs: Vec<Output>) -> Result<Self> {
+        let mut outputs = outputs;
 
-        let has_level11 = output
-            .video
-            .tracks
+        let has_level11 = outputs
             .first()
+            .and_then(|output| output.video.tracks.first())
             .map(|track| track.plugin_node.level11.is_some())
             .context("No track in output.")?;
 
@@ -47,32 +54,17 @@ impl DolbyLabsMDF {
         .into();
 
         if version > CMV40_MIN_VERSION {
-            output.video.tracks.iter_mut().for_each(|track| {
-                track
-                    .plugin_node
-                    .dv_global_data
-                    .mastering_display
-                    .application_type = Some(ApplicationType {
-                    application_type: ApplicationTypeEnum::All,
-                });
-
-                if let Some(ds) = track.plugin_node.dv_global_data.target_displays.as_mut() {
-                    ds.iter_mut().for_each(|d| {
-                        d.application_type = Some(ApplicationType {
-                            application_type: ApplicationTypeEnum::Home,
-                        })
-                    })
-                }
-            });
+            outputs
+                .iter_mut()
+                .flat_map(|output| output.video.tracks.iter_mut())
+                .for_each(|track| track.plugin_node.dv_global_data.update_application_type());
         }
 
         Ok(Self {
             xmlns: version.get_dolby_xmlns(),
             version,
             revision_history: Some(RevisionHistory::new()),
-            outputs: Outputs {
-                outputs: vec![output],
-            },
+            outputs: Outputs { outputs },
         })
     }
 }
@@ -92,7 +84,7 @@ impl IntoCMV29<cmv29::DolbyLabsMDF> for DolbyLabsMDF {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Outputs {
     #[serde(rename = "Output")]
     pub outputs: Vec<Output>,
@@ -106,7 +98,7 @@ impl IntoCMV29<cmv29::Outputs> for Outputs {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Output {
     #[serde(rename = "CompositionName")]
     pub composition_name: String,
@@ -130,6 +122,7 @@ impl IntoCMV29<cmv29::Output> for Output {
             track.shots.iter_mut().for_each(|shots| {
                 shots.iter_mut().for_each(|shot| {
                     shot.source.parent_id = parent_id.clone();
+                    shot.source.in_ = shot.record.in_;
                 })
             })
         });
@@ -166,9 +159,22 @@ impl Output {
             },
         }
     }
+
+    /// Level 5 of shots without it.
+    pub fn level5(&self) -> Level5 {
+        let mut level5 = Level5::from(self.image_aspect_ratio);
+
+        if let Ok(aspect_ratio) =
+            AspectRatio::try_from(vec![self.canvas_aspect_ratio, self.image_aspect_ratio])
+        {
+            level5.aspect_ratio = level5.aspect_ratio.with_new_inner(aspect_ratio);
+        }
+
+        level5
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Video {
     #[serde(rename = "Track")]
     pub tracks: Vec<Track>,
@@ -1,13 +1,13 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
 use dolby_vision::rpu::vdr_dm_data::VdrDmData;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cmv40::Frame;
 use crate::levels::*;
 use crate::metadata::update_levels;
 use crate::{cmv29, IntoCMV29, UUIDv4};
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Shot {
     #[serde(rename = "UniqueID")]
     pub unique_id: UUIDv4,
@@ -17,6 +17,7 @@ pub struct Shot {
     pub plugin_node: ShotPluginNode,
     #[serde(rename = "Frame")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub frames: Option<Vec<Frame>>,
 }
 
@@ -82,13 +83,14 @@ impl IntoCMV29<cmv29::Shot> for Shot {
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ShotPluginNode {
     #[serde(rename = "DVDynamicData")]
     pub dv_dynamic_data: DVDynamicData,
     // Version 5.1.0+
     #[serde(rename = "Level11")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level11: Option<Level11>,
 }
 
@@ -127,24 +129,29 @@ impl From<&VdrDmData> for ShotPluginNode {
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct DVDynamicData {
     #[serde(rename = "Level1")]
     pub level1: Level1,
     #[serde(rename = "Level2")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level2: Option<Vec<Level2>>,
     #[serde(rename = "Level3")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level3: Option<Level3>,
     #[serde(rename = "Level5")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level5: Option<Level5>,
     #[serde(rename = "Level8")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level8: Option<Vec<Level8>>,
     #[serde(rename = "Level9")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level9: Option<Level9>,
 }
 
@@ -214,7 +221,7 @@ impl From<&VdrDmData> for DVDynamicData {
 }
 
 // TODO: Start duration is 1
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Record {
     #[serde(rename = "In")]
     pub in_: usize,
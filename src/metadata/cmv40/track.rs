@@ -5,7 +5,7 @@ use anyhow::{ensure, Result};
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
 use dolby_vision::rpu::vdr_dm_data::VdrDmData;
 use itertools::Itertools;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cmv29::Rate;
 use crate::cmv40::display::Characteristics;
@@ -14,11 +14,11 @@ use crate::display::Chromaticity;
 use crate::levels::*;
 use crate::MDFType::CMV40;
 use crate::{
-    cmv29, display, ColorSpace, ColorSpaceEnum, Encoding, EncodingEnum, IntoCMV29, MDFType,
-    Primaries, SignalRange, SignalRangeEnum, UUIDv4,
+    cmv29, display, ApplicationType, ApplicationTypeEnum, ChromaFormat, ColorSpace, ColorSpaceEnum,
+    Encoding, EncodingEnum, IntoCMV29, MDFType, Primaries, SignalRange, SignalRangeEnum, UUIDv4,
 };
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Track {
     #[serde(rename = "TrackName")]
     pub track_name: String,
@@ -30,11 +30,13 @@ pub struct Track {
     pub color_encoding: ColorEncoding,
     #[serde(rename = "Level6")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level6: Option<Level6>,
     #[serde(rename = "PluginNode")]
     pub plugin_node: TrackPluginNode,
     #[serde(rename = "Shot")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub shots: Option<Vec<Shot>>,
 }
 
@@ -73,7 +75,7 @@ impl IntoCMV29<cmv29::Track> for Track {
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct EditRate(pub [usize; 2]);
 
 impl EditRate {
@@ -82,6 +84,19 @@ impl EditRate {
 
         Ok(())
     }
+
+    /// Reduced fraction, like `24/1` for `48000/2000`.
+    pub fn normalize(self) -> Self {
+        let [mut a, mut b] = self.0;
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+
+        match a {
+            0 => self,
+            gcd => Self(self.0.map(|n| n / gcd)),
+        }
+    }
 }
 
 impl Default for EditRate {
@@ -125,17 +140,19 @@ impl Display for EditRate {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TrackPluginNode {
     #[serde(rename = "DVGlobalData")]
     pub dv_global_data: DVGlobalData,
     // Version 5.1.0+
     #[serde(rename = "Level11")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level11: Option<Level11>,
     // For Version 4.0.2+, level254 should not be None.
     #[serde(rename = "Level254")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub level254: Option<Level254>,
 }
 
@@ -165,7 +182,7 @@ impl From<&VdrDmData> for TrackPluginNode {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColorEncoding {
     #[serde(rename = "Primaries")]
     pub primaries: Primaries,
@@ -177,28 +194,38 @@ pub struct ColorEncoding {
     pub minimum_brightness: usize,
     #[serde(rename = "Encoding")]
     pub encoding: Encoding,
+    // Only in CM v2.9 XML by default, 16 if not set
+    #[serde(rename = "BitDepth")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub bit_depth: Option<usize>,
     #[serde(rename = "ColorSpace")]
     pub color_space: ColorSpace,
+    // Only in CM v2.9 XML by default, 444 if not set
+    #[serde(rename = "ChromaFormat")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub chroma_format: Option<ChromaFormat>,
     #[serde(rename = "SignalRange")]
     pub signal_range: SignalRange,
 }
 
-// TODO: Default is BT.2020 PQ, should provide other options
-impl Default for ColorEncoding {
-    fn default() -> Self {
-        let p = display::Primaries::get_index_primary(2, false).unwrap_or_default();
+impl ColorEncoding {
+    pub const DEFAULT_BIT_DEPTH: usize = 16;
 
+    /// Full range RGB with peak brightness determined by EOTF.
+    pub fn with_primaries(primaries: display::Primaries, encoding: EncodingEnum) -> Self {
         Self {
-            primaries: p.into(),
-            white_point: CMV40(p.white_point),
-            peak_brightness: 10000,
+            primaries: primaries.into(),
+            white_point: CMV40(primaries.white_point),
+            peak_brightness: encoding.peak_brightness(),
             minimum_brightness: 0,
-            encoding: Encoding {
-                encoding: EncodingEnum::Pq,
-            },
+            encoding: Encoding { encoding },
+            bit_depth: None,
             color_space: ColorSpace {
                 color_space: ColorSpaceEnum::Rgb,
             },
+            chroma_format: None,
             signal_range: SignalRange {
                 signal_range: SignalRangeEnum::Computer,
             },
@@ -206,7 +233,16 @@ impl Default for ColorEncoding {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+// BT.2020 PQ
+impl Default for ColorEncoding {
+    fn default() -> Self {
+        let p = display::Primaries::get_index_primary(2, false).unwrap_or_default();
+
+        Self::with_primaries(p, EncodingEnum::Pq)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DVGlobalData {
     // 0
     #[serde(rename = "@level")]
@@ -215,5 +251,25 @@ pub struct DVGlobalData {
     pub mastering_display: Characteristics,
     #[serde(rename = "TargetDisplay")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub target_displays: Option<Vec<Characteristics>>,
 }
+
+impl DVGlobalData {
+    /// Set application type for Version 5.0.0+, if it's not set.
+    pub fn update_application_type(&mut self) {
+        self.mastering_display
+            .application_type
+            .get_or_insert(ApplicationType {
+                application_type: ApplicationTypeEnum::All,
+            });
+
+        if let Some(ds) = self.target_displays.as_mut() {
+            ds.iter_mut().for_each(|d| {
+                d.application_type.get_or_insert(ApplicationType {
+                    application_type: ApplicationTypeEnum::Home,
+                });
+            })
+        }
+    }
+}
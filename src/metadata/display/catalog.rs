@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+
+use crate::display::{
+    Characteristics, Primaries, PREDEFINED_COLORSPACE_PRIMARIES, PREDEFINED_MASTERING_DISPLAYS,
+    PREDEFINED_TARGET_DISPLAYS,
+};
+use crate::{Encoding, EncodingEnum};
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// User-defined displays and primaries, extending the predefined ones.
+///
+/// Predefined entries always take precedence, so a catalog can't change existing IDs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Catalog {
+    #[serde(default)]
+    pub primaries: Vec<CatalogPrimaries>,
+    #[serde(default)]
+    pub mastering_displays: Vec<CatalogDisplay>,
+    #[serde(default)]
+    pub target_displays: Vec<CatalogDisplay>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogPrimaries {
+    pub index: usize,
+    pub name: String,
+    /// Format: `[red_x, red_y, green_x, green_y, blue_x, blue_y, white_x, white_y]`
+    pub values: [f32; 8],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogDisplay {
+    pub id: usize,
+    /// Generated from other fields when not set.
+    pub name: Option<String>,
+    /// Index of predefined or user-defined primaries.
+    pub primaries: usize,
+    pub peak_brightness: usize,
+    #[serde(default)]
+    pub minimum_brightness: f32,
+    #[serde(default)]
+    pub eotf: EncodingEnum,
+    #[serde(default = "CatalogDisplay::default_diagonal_size")]
+    pub diagonal_size: usize,
+}
+
+impl Catalog {
+    /// Parse a catalog from TOML, or JSON when the file extension is `.json`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+
+        let catalog: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => serde_json::from_str(&content)?,
+            _ => toml::from_str(&content)?,
+        };
+
+        catalog
+            .validate()
+            .with_context(|| format!("Invalid display catalog {}", path.display()))?;
+
+        Ok(catalog)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for primaries in &self.primaries {
+            ensure!(
+                primaries.index >= PREDEFINED_COLORSPACE_PRIMARIES.len(),
+                "Primaries index {} is predefined.",
+                primaries.index
+            );
+        }
+
+        for (i, primaries) in self.primaries.iter().enumerate() {
+            ensure!(
+                self.primaries[..i]
+                    .iter()
+                    .all(|p| p.index != primaries.index),
+                "Duplicate primaries index {}.",
+                primaries.index
+            );
+        }
+
+        // IDs must resolve to the same display in all lookups
+        for (kind, displays, predefined) in [
+            (
+                "mastering",
+                &self.mastering_displays,
+                PREDEFINED_MASTERING_DISPLAYS,
+            ),
+            ("target", &self.target_displays, PREDEFINED_TARGET_DISPLAYS),
+        ] {
+            for (i, display) in displays.iter().enumerate() {
+                ensure!(
+                    predefined.iter().all(|d| d[0] != display.id),
+                    "Display ID {} is a predefined {kind} display.",
+                    display.id
+                );
+                ensure!(
+                    displays[..i].iter().all(|d| d.id != display.id),
+                    "Duplicate {kind} display ID {}.",
+                    display.id
+                );
+            }
+        }
+
+        for display in self.mastering_displays.iter().chain(&self.target_displays) {
+            ensure!(
+                display.primaries < PREDEFINED_COLORSPACE_PRIMARIES.len()
+                    || self.get_primaries(display.primaries).is_some(),
+                "Unknown primaries index {} for display {}.",
+                display.primaries,
+                display.id
+            );
+            ensure!(
+                display.peak_brightness > 0,
+                "Invalid peak brightness for display {}.",
+                display.id
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Make the catalog visible to all display lookups.
+    /// It can only be installed once.
+    pub fn install(self) -> Result<()> {
+        if CATALOG.set(self).is_err() {
+            bail!("Display catalog is already installed.");
+        }
+
+        Ok(())
+    }
+
+    /// The installed catalog, if any.
+    pub fn get() -> Option<&'static Self> {
+        CATALOG.get()
+    }
+
+    pub fn get_primaries(&self, index: usize) -> Option<&CatalogPrimaries> {
+        self.primaries.iter().find(|p| p.index == index)
+    }
+
+    pub fn find_primaries(&self, primaries: &Primaries) -> Option<&CatalogPrimaries> {
+        self.primaries
+            .iter()
+            .find(|p| Primaries::from(p.values) == *primaries)
+    }
+}
+
+impl CatalogDisplay {
+    fn default_diagonal_size() -> usize {
+        42
+    }
+
+    pub fn find(list: &[Self], max_luminance: usize, primary: usize) -> Option<&Self> {
+        list.iter()
+            .find(|d| d.peak_brightness == max_luminance && d.primaries == primary)
+    }
+}
+
+impl From<&CatalogDisplay> for Characteristics {
+    fn from(display: &CatalogDisplay) -> Self {
+        let mut result = Self {
+            name: String::new(),
+            id: display.id,
+            primary_index: display.primaries,
+            primaries: Primaries::get_index_primary(display.primaries, false).unwrap_or_default(),
+            peak_brightness: display.peak_brightness,
+            minimum_brightness: display.minimum_brightness,
+            encoding: Encoding {
+                encoding: display.eotf,
+            },
+            diagonal_size: display.diagonal_size,
+        };
+
+        match &display.name {
+            Some(name) => result.name.clone_from(name),
+            None => result.update_name(),
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Catalog, CatalogDisplay};
+    use crate::display::{Characteristics, Primaries};
+
+    const CATALOG: &str = r#"
+        [[primaries]]
+        index = 19
+        name = "Test OLED"
+        values = [0.68, 0.31, 0.25, 0.70, 0.14, 0.05, 0.3127, 0.329]
+
+        [[target_displays]]
+        id = 200
+        primaries = 19
+        peak_brightness = 1500
+        minimum_brightness = 0.0005
+        diagonal_size = 31
+
+        [[mastering_displays]]
+        id = 201
+        name = "Test Monitor"
+        primaries = 2
+        peak_brightness = 1500
+        eotf = "pq"
+    "#;
+
+    // The catalog is not installed, as it's global to all tests
+    #[test]
+    fn test_catalog() {
+        let catalog: Catalog = toml::from_str(CATALOG).unwrap();
+        catalog.validate().unwrap();
+
+        let json: Catalog = serde_json::from_str(
+            r#"{"target_displays": [{"id": 202, "primaries": 20, "peak_brightness": 800}]}"#,
+        )
+        .unwrap();
+        assert!(json.validate().is_err());
+
+        let primaries = Primaries::from([0.68, 0.31, 0.25, 0.70, 0.14, 0.05, 0.3127, 0.329]);
+        assert_eq!(
+            catalog.find_primaries(&primaries).map(|p| p.index),
+            Some(19)
+        );
+        assert_eq!(catalog.get_primaries(19).unwrap().name, "Test OLED");
+
+        let target = CatalogDisplay::find(&catalog.target_displays, 1500, 19).unwrap();
+        assert_eq!(target.id, 200);
+        assert!(CatalogDisplay::find(&catalog.target_displays, 1000, 2).is_none());
+
+        let target = Characteristics::from(target);
+        assert_eq!((target.id, target.primary_index), (200, 19));
+        assert_eq!(target.diagonal_size, 31);
+
+        let source = Characteristics::from(&catalog.mastering_displays[0]);
+        assert_eq!(source.name, "Test Monitor");
+        assert_eq!(source.diagonal_size, 42);
+    }
+
+    #[test]
+    fn test_display_ids() {
+        let catalog: Catalog = toml::from_str(
+            r#"
+            [[target_displays]]
+            id = 49
+            primaries = 2
+            peak_brightness = 1500
+            "#,
+        )
+        .unwrap();
+        let error = catalog.validate().unwrap_err();
+        assert!(error.to_string().contains("49"));
+
+        let catalog: Catalog = toml::from_str(
+            r#"
+            [[mastering_displays]]
+            id = 100
+            primaries = 2
+            peak_brightness = 1500
+
+            [[mastering_displays]]
+            id = 100
+            primaries = 0
+            peak_brightness = 2000
+            "#,
+        )
+        .unwrap();
+        let error = catalog.validate().unwrap_err();
+        assert!(error.to_string().contains("100"));
+
+        // Mastering and target displays have separate IDs
+        let catalog: Catalog = toml::from_str(
+            r#"
+            [[mastering_displays]]
+            id = 100
+            primaries = 2
+            peak_brightness = 1500
+
+            [[target_displays]]
+            id = 100
+            primaries = 2
+            peak_brightness = 1500
+            "#,
+        )
+        .unwrap();
+        catalog.validate().unwrap();
+    }
+}
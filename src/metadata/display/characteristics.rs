@@ -1,5 +1,5 @@
 use std::hash::{Hash, Hasher};
-use std::intrinsics::transmute;
+use std::mem::transmute;
 
 use dolby_vision::rpu::extension_metadata::blocks::{
     ExtMetadataBlock, ExtMetadataBlockInfo, ExtMetadataBlockLevel10, ExtMetadataBlockLevel2,
@@ -8,9 +8,14 @@ use dolby_vision::rpu::extension_metadata::blocks::{
 use dolby_vision::rpu::vdr_dm_data::VdrDmData;
 use itertools::Itertools;
 
-use crate::display::{PREDEFINED_MASTERING_DISPLAYS, PREDEFINED_TARGET_DISPLAYS, RPU_PQ_MAX};
+use crate::display::{
+    Catalog, CatalogDisplay, PREDEFINED_MASTERING_DISPLAYS, PREDEFINED_TARGET_DISPLAYS, RPU_PQ_MAX,
+};
 use crate::metadata::display::primary::Primaries;
-use crate::{display, Encoding, EncodingEnum};
+use crate::{display, rpu_pq_u12_from_f32, Encoding, EncodingEnum};
+
+// Minimum brightness of custom mastering display in nits, when not set
+const DEFAULT_MINIMUM_BRIGHTNESS: f32 = 0.005;
 
 #[derive(Debug, Clone)]
 pub struct Characteristics {
@@ -60,7 +65,9 @@ impl Characteristics {
             2 => "BT.2020",
             5 => "P3, DCI",
             9 => "WCG, D65",
-            _ => "Custom",
+            index => Catalog::get()
+                .and_then(|catalog| catalog.get_primaries(index))
+                .map_or("Custom", |p| p.name.as_str()),
         };
 
         let eotf = match self.encoding.encoding {
@@ -122,9 +129,12 @@ impl Characteristics {
             0
         };
 
-        if let Some(target) =
-            Self::get_display(PREDEFINED_TARGET_DISPLAYS, max_luminance, primary_index)
-        {
+        if let Some(target) = Self::get_display(
+            PREDEFINED_TARGET_DISPLAYS,
+            |catalog| &catalog.target_displays,
+            max_luminance,
+            primary_index,
+        ) {
             target
         } else {
             let mut target = Self {
@@ -142,12 +152,22 @@ impl Characteristics {
     }
 
     fn get_target(block: &ExtMetadataBlockLevel8) -> Option<Self> {
-        let index = block.target_display_index as usize;
+        Self::get_target_with_id(block.target_display_index as usize)
+    }
 
+    /// Predefined or user-defined target display.
+    pub fn get_target_with_id(id: usize) -> Option<Self> {
         PREDEFINED_TARGET_DISPLAYS
             .iter()
-            .find(|d| (**d)[0] == index)
+            .find(|d| (**d)[0] == id)
             .map(|d| Self::from(*d))
+            .or_else(|| {
+                Catalog::get()?
+                    .target_displays
+                    .iter()
+                    .find(|d| d.id == id)
+                    .map(Self::from)
+            })
     }
 
     pub fn get_targets(vdr: &VdrDmData) -> Option<Vec<Self>> {
@@ -203,9 +223,12 @@ impl Characteristics {
             _ => Characteristics::max_u16_from_rpu_pq_u12(vdr.source_max_pq),
         };
 
-        if let Some(source) =
-            Self::get_display(PREDEFINED_MASTERING_DISPLAYS, max_luminance, primary_index)
-        {
+        if let Some(source) = Self::get_display(
+            PREDEFINED_MASTERING_DISPLAYS,
+            |catalog| &catalog.mastering_displays,
+            max_luminance,
+            primary_index,
+        ) {
             source
         } else {
             let mut source = Self::default_source();
@@ -239,15 +262,89 @@ impl Characteristics {
         }
     }
 
+    /// Predefined or user-defined mastering display.
+    pub fn get_source(id: usize) -> Option<Self> {
+        PREDEFINED_MASTERING_DISPLAYS
+            .iter()
+            .find(|d| (**d)[0] == id)
+            .map(|d| Self::from(*d))
+            .or_else(|| {
+                Catalog::get()?
+                    .mastering_displays
+                    .iter()
+                    .find(|d| d.id == id)
+                    .map(Self::from)
+            })
+    }
+
+    /// Mastering display with custom primaries and luminance range.
+    /// A predefined or user-defined one is used if it's identical in code values,
+    /// or has the same peak brightness and primaries when the minimum brightness is not set.
+    pub fn custom_source(
+        primaries: Primaries,
+        peak_brightness: usize,
+        minimum_brightness: Option<f32>,
+    ) -> Self {
+        let primary_index = primaries.get_index();
+
+        let source = primary_index.and_then(|index| {
+            Self::get_display(
+                PREDEFINED_MASTERING_DISPLAYS,
+                |catalog| &catalog.mastering_displays,
+                peak_brightness,
+                index,
+            )
+        });
+
+        let min_pq = |nits: f32| rpu_pq_u12_from_f32(display::l2pq(nits));
+
+        match (source, minimum_brightness) {
+            (Some(source), None) => source,
+            (Some(source), Some(minimum_brightness))
+                if min_pq(source.minimum_brightness) == min_pq(minimum_brightness) =>
+            {
+                source
+            }
+            _ => {
+                let mut source = Self {
+                    // For convenience, use PQ of peak brightness as custom mastering display id
+                    id: rpu_pq_u12_from_f32(display::l2pq(peak_brightness as f32)) as usize,
+                    // Random invalid value
+                    primary_index: primary_index.unwrap_or(255),
+                    primaries,
+                    peak_brightness,
+                    minimum_brightness: minimum_brightness.unwrap_or(DEFAULT_MINIMUM_BRIGHTNESS),
+                    ..Self::default_source()
+                };
+
+                source.update_name();
+                source
+            }
+        }
+    }
+
     /*pub fn update_luminance_range_with_l6_block(&mut self, block: &ExtMetadataBlockLevel6) {
         self.peak_brightness = block.max_display_mastering_luminance as usize;
         self.minimum_brightness = block.min_display_mastering_luminance as f32 / RPU_L6_MIN_FACTOR;
     }*/
 
-    fn get_display(list: &[[usize; 6]], max_luminance: usize, primary: usize) -> Option<Self> {
+    /// Predefined display, or user-defined display from the installed catalog.
+    fn get_display<F>(
+        list: &[[usize; 6]],
+        catalog_list: F,
+        max_luminance: usize,
+        primary: usize,
+    ) -> Option<Self>
+    where
+        F: Fn(&Catalog) -> &[CatalogDisplay],
+    {
         list.iter()
             .find(|d| (**d)[2] == max_luminance && (**d)[1] == primary)
             .map(|d| Self::from(*d))
+            .or_else(|| {
+                let catalog = Catalog::get()?;
+                CatalogDisplay::find(catalog_list(catalog), max_luminance, primary).map(Self::from)
+            })
     }
 }
 
@@ -24,6 +24,14 @@ impl Hash for Chromaticity {
     }
 }
 
+impl TryFrom<Vec<f32>> for Chromaticity {
+    type Error = Vec<f32>;
+
+    fn try_from(vec: Vec<f32>) -> Result<Self, Self::Error> {
+        Ok(Self(vec.try_into()?))
+    }
+}
+
 impl IntoIterator for Chromaticity {
     type Item = f32;
     type IntoIter = array::IntoIter<Self::Item, 2>;
@@ -1,7 +1,9 @@
+pub use catalog::{Catalog, CatalogDisplay, CatalogPrimaries};
 pub use characteristics::Characteristics;
 pub use chromaticity::Chromaticity;
 pub use primary::Primaries;
 
+mod catalog;
 mod characteristics;
 mod chromaticity;
 mod primary;
@@ -72,15 +74,30 @@ const ST2084_C2: f32 = (2413.0 / 4096.0) * 32.0;
 const ST2084_C3: f32 = (2392.0 / 4096.0) * 32.0;
 
 pub fn pq2l(pq: f32) -> f32 {
-    let y = ((pq.powf(1.0 / ST2084_M2) - ST2084_C1)
+    let y = ((pq.powf(1.0 / ST2084_M2) - ST2084_C1).max(0.0)
         / (ST2084_C2 - ST2084_C3 * pq.powf(1.0 / ST2084_M2)))
     .powf(1.0 / ST2084_M1);
 
     y * ST2084_Y_MAX
 }
 
+/// Inverse of `pq2l`, nits are clamped to 0 to 10000.
+/// Computed in f64, so that code values match the ones from `dolby_vision::utils::nits_to_pq`.
+pub fn l2pq(nits: f32) -> f32 {
+    let y = (nits as f64 / ST2084_Y_MAX as f64)
+        .clamp(0.0, 1.0)
+        .powf(ST2084_M1 as f64);
+
+    ((ST2084_C1 as f64 + ST2084_C2 as f64 * y) / (1.0 + ST2084_C3 as f64 * y))
+        .powf(ST2084_M2 as f64) as f32
+}
+
 pub fn find_target_id(max: usize, primary: usize) -> Option<usize> {
-    get_display_id(PREDEFINED_TARGET_DISPLAYS, max, primary)
+    get_display_id(PREDEFINED_TARGET_DISPLAYS, max, primary).or_else(|| {
+        Catalog::get()
+            .and_then(|catalog| CatalogDisplay::find(&catalog.target_displays, max, primary))
+            .map(|d| d.id)
+    })
 }
 
 fn get_display_id(list: &[[usize; 6]], max_luminance: usize, primary: usize) -> Option<usize> {
@@ -88,3 +105,38 @@ fn get_display_id(list: &[[usize; 6]], max_luminance: usize, primary: usize) ->
         .find(|t| (**t)[2] == max_luminance && (**t)[1] == primary)
         .map(|d| d[0])
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{l2pq, pq2l, RPU_PQ_MAX};
+    use crate::rpu_pq_u12_from_f32;
+
+    #[test]
+    fn test_l2pq() {
+        assert_eq!(rpu_pq_u12_from_f32(l2pq(0.0)), 0);
+        assert_eq!(rpu_pq_u12_from_f32(l2pq(100.0)), 2081);
+        assert_eq!(rpu_pq_u12_from_f32(l2pq(1000.0)), 3079);
+        assert_eq!(rpu_pq_u12_from_f32(l2pq(10000.0)), 4095);
+        assert_eq!(l2pq(-1.0), l2pq(0.0));
+    }
+
+    proptest! {
+        #[test]
+        fn test_pq_round_trip(u in 0..=4095_u16) {
+            let nits = pq2l(u as f32 / RPU_PQ_MAX);
+            let v = rpu_pq_u12_from_f32(l2pq(nits));
+
+            prop_assert!(u.abs_diff(v) <= 1, "{u} -> {nits} nits -> {v}");
+        }
+
+        #[test]
+        fn test_nits_round_trip(nits in 0.01_f32..10000.0) {
+            let pq = l2pq(nits);
+
+            prop_assert!((0.0..=1.0).contains(&pq));
+            prop_assert!((pq2l(pq) - nits).abs() <= nits * 1e-3);
+        }
+    }
+}
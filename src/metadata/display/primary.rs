@@ -6,7 +6,7 @@ use dolby_vision::rpu::extension_metadata::blocks::{
 use dolby_vision::rpu::vdr_dm_data::VdrDmData;
 
 use crate::display::chromaticity::Chromaticity;
-use crate::display::PREDEFINED_COLORSPACE_PRIMARIES;
+use crate::display::{Catalog, PREDEFINED_COLORSPACE_PRIMARIES};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Primaries {
@@ -25,7 +25,34 @@ impl Primaries {
         }) / 32767.0
     }
 
+    /// Inverse of `f32_from_rpu_u16`.
+    pub fn rpu_u16_from_f32(f: f32) -> u16 {
+        let i = (f * 32767.0).round() as i32;
+
+        (if i < 0 { i + 65536 } else { i }) as u16
+    }
+
+    pub fn to_rpu_u16(self) -> [u16; 8] {
+        let mut result = [0; 8];
+
+        for (i, f) in result.iter_mut().zip(self) {
+            *i = Self::rpu_u16_from_f32(f)
+        }
+
+        result
+    }
+
+    /// Index of predefined or user-defined primaries.
     pub fn get_index(&self) -> Option<usize> {
+        self.get_predefined_index().or_else(|| {
+            Catalog::get()
+                .and_then(|catalog| catalog.find_primaries(self))
+                .map(|p| p.index)
+        })
+    }
+
+    /// Index of predefined primaries, which is the only valid index in RPU.
+    pub fn get_predefined_index(&self) -> Option<usize> {
         PREDEFINED_COLORSPACE_PRIMARIES
             .iter()
             .enumerate()
@@ -35,7 +62,15 @@ impl Primaries {
 
     pub fn get_index_primary(index: usize, is_target: bool) -> Option<Self> {
         let index_max = PREDEFINED_COLORSPACE_PRIMARIES.len();
-        let index = if index >= index_max || is_target && index > 8 {
+
+        if index >= index_max {
+            // User-defined primaries are valid for any display
+            return Catalog::get()
+                .and_then(|catalog| catalog.get_primaries(index))
+                .map(|p| Primaries::from(p.values));
+        }
+
+        let index = if is_target && index > 8 {
             None
         } else {
             Some(index)
@@ -83,6 +118,14 @@ impl From<[f32; 8]> for Primaries {
     }
 }
 
+impl TryFrom<Vec<f32>> for Primaries {
+    type Error = Vec<f32>;
+
+    fn try_from(vec: Vec<f32>) -> Result<Self, Self::Error> {
+        Ok(Self::from(<[f32; 8]>::try_from(vec)?))
+    }
+}
+
 impl From<[u16; 8]> for Primaries {
     fn from(p: [u16; 8]) -> Self {
         let mut result = [0.0f32; 8];
@@ -1,12 +1,12 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel1;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::metadata::MDFType::*;
-use crate::metadata::{IntoCMV29, MDFType};
+use crate::metadata::{IntoCMV29, IntoCMV40, MDFType};
 
-use super::ImageCharacter;
+use super::{rpu_pq_u12_from_f32, ImageCharacter};
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Level1 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -23,6 +23,27 @@ impl From<&ExtMetadataBlockLevel1> for Level1 {
     }
 }
 
+impl From<&Level1> for ExtMetadataBlockLevel1 {
+    fn from(level1: &Level1) -> Self {
+        let [min_pq, avg_pq, max_pq] = level1
+            .image_character
+            .into_inner()
+            .0
+            .map(rpu_pq_u12_from_f32);
+
+        Self::new(min_pq, max_pq, avg_pq)
+    }
+}
+
+impl IntoCMV40<Self> for Level1 {
+    fn into_cmv40(self) -> Self {
+        Self {
+            level: 1,
+            image_character: self.image_character.into_cmv40(),
+        }
+    }
+}
+
 impl IntoCMV29<Self> for Level1 {
     fn into_cmv29(self) -> Self {
         Self {
@@ -1,7 +1,7 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel11;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Level11 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -12,6 +12,7 @@ pub struct Level11 {
     // FIXME: Rename
     #[serde(rename = "ExtensionProperties")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub extension_properties: Option<u8>,
 }
 
@@ -32,8 +33,19 @@ impl From<&ExtMetadataBlockLevel11> for Level11 {
             level: 11,
             content_type: block.content_type,
             intended_white_point: block.whitepoint,
-            // TODO: byte3?
-            extension_properties: None,
+            // Only written when set, to keep the output of existing XML
+            extension_properties: (block.reserved_byte3 != 0).then_some(block.reserved_byte3),
+        }
+    }
+}
+
+impl From<&Level11> for ExtMetadataBlockLevel11 {
+    fn from(level11: &Level11) -> Self {
+        Self {
+            content_type: level11.content_type,
+            whitepoint: level11.intended_white_point,
+            reserved_byte3: level11.extension_properties.unwrap_or(0),
+            ..Default::default()
         }
     }
 }
@@ -1,15 +1,16 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel2;
 use serde::ser::SerializeStruct;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::TrimSixField;
 use crate::display::find_target_id;
-use crate::f32_from_rpu_u12_with_bias;
 use crate::metadata::display::Characteristics;
 use crate::metadata::MDFType::*;
-use crate::metadata::{IntoCMV29, MDFType, WithTid};
+use crate::metadata::{IntoCMV29, IntoCMV40, MDFType, WithTid};
+use crate::{f32_from_rpu_u12_with_bias, rpu_u12_with_bias_from_f32};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "Level2Padded")]
 pub struct Level2 {
     pub level: u8,
     pub tid: usize,
@@ -54,6 +55,36 @@ impl Serialize for Level2 {
     }
 }
 
+// Trim in XML is padded with three zeros
+#[derive(Deserialize)]
+struct Level2Padded {
+    #[serde(rename = "@level")]
+    level: u8,
+    #[serde(rename = "TID")]
+    tid: usize,
+    #[serde(rename = "Trim")]
+    trim: MDFType<[f32; 9]>,
+}
+
+impl From<Level2Padded> for Level2 {
+    fn from(padded: Level2Padded) -> Self {
+        Self {
+            level: padded.level,
+            ..Self::with_padded_trim(padded.tid, padded.trim)
+        }
+    }
+}
+
+impl IntoCMV40<Self> for Level2 {
+    fn into_cmv40(self) -> Self {
+        Self {
+            level: 2,
+            tid: self.tid,
+            trim: self.trim.into_cmv40(),
+        }
+    }
+}
+
 impl IntoCMV29<Self> for Level2 {
     fn into_cmv29(self) -> Self {
         Self {
@@ -65,6 +96,22 @@ impl IntoCMV29<Self> for Level2 {
 }
 
 impl Level2 {
+    pub fn with_padded_trim(tid: usize, trim: MDFType<[f32; 9]>) -> Self {
+        let mut trim = trim;
+        let mut new_trim = TrimSixField::default();
+        new_trim
+            .0
+            .iter_mut()
+            .zip(trim.into_inner().into_iter().skip(3))
+            .for_each(|(t, s)| *t = s);
+
+        Self {
+            level: 2,
+            tid,
+            trim: trim.with_new_inner(new_trim),
+        }
+    }
+
     pub fn with_primary_index(block: &ExtMetadataBlockLevel2, primary: Option<usize>) -> Self {
         // identical definition for all negative values, use -1 for v2.0.5+
         let ms_weight = if block.ms_weight < 0 {
@@ -101,6 +148,29 @@ impl Level2 {
             trim: CMV40(trim),
         }
     }
+
+    pub fn to_block_with_target_max_pq(&self, target_max_pq: u16) -> ExtMetadataBlockLevel2 {
+        let mut trim = self.trim.into_inner();
+        let ms_weight = trim.0[5];
+
+        trim.lgg_to_sop();
+        let [trim_slope, trim_offset, trim_power, trim_chroma_weight, trim_saturation_gain, _] =
+            trim.to_rpu_u12();
+
+        ExtMetadataBlockLevel2 {
+            target_max_pq,
+            trim_slope,
+            trim_offset,
+            trim_power,
+            trim_chroma_weight,
+            trim_saturation_gain,
+            ms_weight: if ms_weight < 0.0 {
+                -1
+            } else {
+                rpu_u12_with_bias_from_f32(ms_weight) as i16
+            },
+        }
+    }
 }
 
 impl From<&ExtMetadataBlockLevel2> for Level2 {
@@ -1,7 +1,7 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel254;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Level254 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -31,3 +31,12 @@ impl From<&ExtMetadataBlockLevel254> for Level254 {
         }
     }
 }
+
+impl From<&Level254> for ExtMetadataBlockLevel254 {
+    fn from(level254: &Level254) -> Self {
+        Self {
+            dm_mode: level254.dm_mode,
+            dm_version_index: level254.dm_version,
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel3;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::MDFType::CMV40;
-use crate::{ImageCharacter, MDFType};
+use crate::{rpu_u12_with_bias_from_f32, ImageCharacter, MDFType};
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Level3 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -30,3 +30,19 @@ impl From<&ExtMetadataBlockLevel3> for Level3 {
         }
     }
 }
+
+impl From<&Level3> for ExtMetadataBlockLevel3 {
+    fn from(level3: &Level3) -> Self {
+        let [min_pq_offset, avg_pq_offset, max_pq_offset] = level3
+            .l1_offset
+            .into_inner()
+            .0
+            .map(rpu_u12_with_bias_from_f32);
+
+        Self {
+            min_pq_offset,
+            max_pq_offset,
+            avg_pq_offset,
+        }
+    }
+}
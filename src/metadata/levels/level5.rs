@@ -1,14 +1,14 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel5;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
 use crate::metadata::levels::UHD_CANVAS;
 use crate::MDFType::CMV40;
-use crate::{IntoCMV29, MDFType};
+use crate::{IntoCMV29, IntoCMV40, MDFType};
 
 use super::AspectRatio;
 
-#[derive(Debug, Clone, Serialize, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct Level5 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -40,6 +40,15 @@ impl From<f32> for Level5 {
     }
 }
 
+impl IntoCMV40<Self> for Level5 {
+    fn into_cmv40(self) -> Self {
+        Self {
+            level: 5,
+            aspect_ratio: self.aspect_ratio.into_cmv40(),
+        }
+    }
+}
+
 impl IntoCMV29<Self> for Level5 {
     fn into_cmv29(self) -> Self {
         Self {
@@ -73,6 +82,32 @@ impl Level5 {
             aspect_ratio: CMV40(AspectRatio([canvas_ar, image_ar])),
         }
     }
+
+    /// Active area offsets of the image aspect ratio in the given canvas,
+    /// the aspect ratio of canvas in metadata is ignored.
+    pub fn to_block_with_canvas(&self, canvas: (usize, usize)) -> ExtMetadataBlockLevel5 {
+        let (width, height) = (canvas.0 as f32, canvas.1 as f32);
+        let canvas_ar = width / height;
+        let (_, image_ar) = self.get_ar();
+
+        let (horizontal_crop, vertical_crop) = if (image_ar - canvas_ar).abs() < f32::EPSILON {
+            (0.0, 0.0)
+        } else if image_ar > canvas_ar {
+            (0.0, (height - width / image_ar).round())
+        } else {
+            ((width - image_ar * height).round(), 0.0)
+        };
+
+        let left = (horizontal_crop / 2.0).trunc();
+        let top = (vertical_crop / 2.0).trunc();
+
+        ExtMetadataBlockLevel5::from_offsets(
+            left as u16,
+            (horizontal_crop - left) as u16,
+            top as u16,
+            (vertical_crop - top) as u16,
+        )
+    }
 }
 
 impl PartialOrd<Self> for Level5 {
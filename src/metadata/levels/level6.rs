@@ -1,7 +1,13 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel6;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+use crate::display::pq2l;
+use crate::Level1;
+
+// Relative difference allowed between Level 6 and the estimated one
+const LEVEL6_TOLERANCE: f32 = 0.2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Level6 {
     #[serde(rename = "@level")]
     pub level: usize,
@@ -30,3 +36,25 @@ impl From<&ExtMetadataBlockLevel6> for Level6 {
         }
     }
 }
+
+impl Level6 {
+    /// Update MaxCLL and MaxFALL with a frame, estimated from max and average of Level 1.
+    pub fn update_with_level1(&mut self, level1: &Level1) {
+        let [_, avg, max] = level1.image_character.into_inner().0.map(pq2l);
+
+        self.max_cll = self.max_cll.max(max.round() as usize);
+        self.max_fall = self.max_fall.max(avg.round() as usize);
+    }
+
+    /// Check if MaxCLL and MaxFALL are close to the estimated ones, zero values are ignored.
+    pub fn is_close_to(&self, estimated: &Self) -> bool {
+        [
+            (self.max_cll, estimated.max_cll),
+            (self.max_fall, estimated.max_fall),
+        ]
+        .into_iter()
+        .all(|(value, estimated)| {
+            value == 0 || value.abs_diff(estimated) as f32 <= value as f32 * LEVEL6_TOLERANCE
+        })
+    }
+}
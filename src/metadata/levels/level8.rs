@@ -1,12 +1,12 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel8;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::TrimSixField;
 use crate::metadata::WithTid;
-use crate::MDFType;
 use crate::MDFType::CMV40;
+use crate::{rpu_u12_with_bias_from_f32, MDFType};
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Level8 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -83,3 +83,55 @@ impl From<&ExtMetadataBlockLevel8> for Level8 {
         }
     }
 }
+
+impl From<&Level8> for ExtMetadataBlockLevel8 {
+    fn from(level8: &Level8) -> Self {
+        let mut trim = level8.l8_trim.into_inner();
+        trim.lgg_to_sop();
+
+        let [trim_slope, trim_offset, trim_power, trim_chroma_weight, trim_saturation_gain, ms_weight] =
+            trim.to_rpu_u12();
+        let target_mid_contrast = rpu_u12_with_bias_from_f32(level8.mid_contrast_bias);
+        let clip_trim = rpu_u12_with_bias_from_f32(level8.highlight_clipping);
+        let sat = level8.sat_vector_field.into_inner().to_rpu_u8();
+        let hue = level8.hue_vector_field.into_inner().to_rpu_u8();
+
+        // Only write the trims which are modified
+        let length = if hue.iter().any(|v| *v != 128) {
+            25
+        } else if sat.iter().any(|v| *v != 128) {
+            19
+        } else if clip_trim != 2048 {
+            13
+        } else if target_mid_contrast != 2048 {
+            12
+        } else {
+            10
+        };
+
+        Self {
+            length,
+            target_display_index: level8.tid,
+            trim_slope,
+            trim_offset,
+            trim_power,
+            trim_chroma_weight,
+            trim_saturation_gain,
+            ms_weight,
+            target_mid_contrast,
+            clip_trim,
+            saturation_vector_field0: sat[0],
+            saturation_vector_field1: sat[1],
+            saturation_vector_field2: sat[2],
+            saturation_vector_field3: sat[3],
+            saturation_vector_field4: sat[4],
+            saturation_vector_field5: sat[5],
+            hue_vector_field0: hue[0],
+            hue_vector_field1: hue[1],
+            hue_vector_field2: hue[2],
+            hue_vector_field3: hue[3],
+            hue_vector_field4: hue[4],
+            hue_vector_field5: hue[5],
+        }
+    }
+}
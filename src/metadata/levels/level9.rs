@@ -1,10 +1,10 @@
 use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel9;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::MDFType::CMV40;
 use crate::{display, MDFType};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Level9 {
     #[serde(rename = "@level")]
     pub level: u8,
@@ -31,3 +31,33 @@ impl From<&ExtMetadataBlockLevel9> for Level9 {
         display::Primaries::from(block).into()
     }
 }
+
+impl From<&Level9> for ExtMetadataBlockLevel9 {
+    fn from(level9: &Level9) -> Self {
+        let primaries = level9.source_color_primary.into_inner();
+
+        match primaries.get_predefined_index() {
+            Some(index) => Self {
+                length: 1,
+                source_primary_index: index as u8,
+                ..Default::default()
+            },
+            None => {
+                let p = primaries.to_rpu_u16();
+
+                Self {
+                    length: 17,
+                    source_primary_index: 255,
+                    source_primary_red_x: p[0],
+                    source_primary_red_y: p[1],
+                    source_primary_green_x: p[2],
+                    source_primary_green_y: p[3],
+                    source_primary_blue_x: p[4],
+                    source_primary_blue_y: p[5],
+                    source_primary_white_x: p[6],
+                    source_primary_white_y: p[7],
+                }
+            }
+        }
+    }
+}
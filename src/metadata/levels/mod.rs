@@ -48,6 +48,35 @@ pub fn f32_from_rpu_u8_with_bias(u: u8) -> f32 {
     (u as f32 - RPU_U8_BIAS) / RPU_U8_BIAS
 }
 
+/// Inverse of `f32_from_rpu_u12_with_bias`.
+/// 4095 stands for 1.0, so only values closer to 1.0 than to 4094 are mapped to it.
+pub fn rpu_u12_with_bias_from_f32(f: f32) -> u16 {
+    let u = f * RPU_U12_BIAS + RPU_U12_BIAS;
+
+    if u >= 4095.0 {
+        4095
+    } else {
+        u.round().clamp(0.0, 4094.0) as u16
+    }
+}
+
+/// Inverse of `f32_from_rpu_u8_with_bias`.
+/// 255 stands for 1.0, so only values closer to 1.0 than to 254 are mapped to it.
+pub fn rpu_u8_with_bias_from_f32(f: f32) -> u8 {
+    let u = f * RPU_U8_BIAS + RPU_U8_BIAS;
+
+    if u >= 255.0 {
+        255
+    } else {
+        u.round().clamp(0.0, 254.0) as u8
+    }
+}
+
+/// PQ in range 0 to 1 to 12-bit code value, clamped.
+pub fn rpu_pq_u12_from_f32(f: f32) -> u16 {
+    (f * RPU_PQ_MAX).round().clamp(0.0, RPU_PQ_MAX) as u16
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct TrimSixField([f32; 6]);
 
@@ -65,6 +94,37 @@ impl TrimSixField {
         self.0[1] = gain.clamp(-1.0, 1.0);
         self.0[2] = gamma;
     }
+
+    pub fn lgg_to_sop(&mut self) {
+        let lift = self.0[0];
+        let gain = self.0[1];
+        let gamma = self.0[2].clamp(-1.0, 1.0);
+
+        let slope = (gain + 2.0) * (1.0 - lift / 2.0) - 2.0;
+        let offset = (gain + 2.0) * lift / 2.0;
+        let power = 4.0 / (gamma + 2.0) - 2.0;
+
+        self.0[0] = slope;
+        self.0[1] = offset;
+        self.0[2] = power;
+    }
+
+    /// Biased 12-bit code values, `ms_weight` is handled by the caller.
+    pub fn to_rpu_u12(self) -> [u16; 6] {
+        self.0.map(rpu_u12_with_bias_from_f32)
+    }
+
+    pub fn to_rpu_u8(self) -> [u8; 6] {
+        self.0.map(rpu_u8_with_bias_from_f32)
+    }
+}
+
+impl TryFrom<Vec<f32>> for TrimSixField {
+    type Error = Vec<f32>;
+
+    fn try_from(vec: Vec<f32>) -> Result<Self, Self::Error> {
+        Ok(Self(vec.try_into()?))
+    }
 }
 
 impl IntoIterator for TrimSixField {
@@ -101,6 +161,14 @@ impl Add for ImageCharacter {
     }
 }
 
+impl TryFrom<Vec<f32>> for ImageCharacter {
+    type Error = Vec<f32>;
+
+    fn try_from(vec: Vec<f32>) -> Result<Self, Self::Error> {
+        Ok(Self(vec.try_into()?))
+    }
+}
+
 impl From<&ExtMetadataBlockLevel1> for ImageCharacter {
     fn from(block: &ExtMetadataBlockLevel1) -> Self {
         Self([
@@ -133,6 +201,14 @@ impl IntoIterator for ImageCharacter {
 #[derive(Clone, Copy, Debug)]
 pub struct AspectRatio([f32; 2]);
 
+impl TryFrom<Vec<f32>> for AspectRatio {
+    type Error = Vec<f32>;
+
+    fn try_from(vec: Vec<f32>) -> Result<Self, Self::Error> {
+        Ok(Self(vec.try_into()?))
+    }
+}
+
 impl IntoIterator for AspectRatio {
     type Item = f32;
     type IntoIter = array::IntoIter<Self::Item, 2>;
@@ -178,6 +254,8 @@ impl Hash for AspectRatio {
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
     #[test]
     fn test_sop_to_lgg() {
@@ -193,4 +271,107 @@ mod tests {
 
         assert_eq!(trim.0, [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
     }
+
+    #[test]
+    fn test_lgg_to_sop() {
+        let sop = [2100, 2000, 2200, 2048, 2048, 2048].map(f32_from_rpu_u12_with_bias);
+
+        let mut trim = TrimSixField(sop);
+        trim.sop_to_lgg();
+        trim.lgg_to_sop();
+
+        assert_eq!(trim.to_rpu_u12(), [2100, 2000, 2200, 2048, 2048, 2048]);
+    }
+
+    #[test]
+    fn test_rpu_u12_with_bias_from_f32() {
+        for u in [0, 1, 2047, 2048, 2049, 4094, 4095] {
+            assert_eq!(rpu_u12_with_bias_from_f32(f32_from_rpu_u12_with_bias(u)), u);
+        }
+
+        for u in [0, 127, 128, 129, 254, 255] {
+            assert_eq!(rpu_u8_with_bias_from_f32(f32_from_rpu_u8_with_bias(u)), u);
+        }
+    }
+
+    #[test]
+    fn test_level6_from_level1() {
+        let mut level6 = Level6::default();
+
+        // 0, 100 and 1000 nits
+        for pq in [[0.0, 0.0, 0.0], [0.0, 0.5080784, 0.7518271]] {
+            level6.update_with_level1(&Level1 {
+                level: 1,
+                image_character: crate::MDFType::CMV40(ImageCharacter(pq)),
+            });
+        }
+
+        assert_eq!((level6.max_cll, level6.max_fall), (1000, 100));
+
+        let rpu_level6 = Level6 {
+            max_cll: 1100,
+            max_fall: 0,
+            ..Default::default()
+        };
+        assert!(rpu_level6.is_close_to(&level6));
+
+        let rpu_level6 = Level6 {
+            max_cll: 4000,
+            ..rpu_level6
+        };
+        assert!(!rpu_level6.is_close_to(&level6));
+    }
+
+    proptest! {
+        #[test]
+        fn test_rpu_u12_round_trip(u in 0..=4095_u16, f in -1.0_f32..=1.0) {
+            prop_assert_eq!(rpu_u12_with_bias_from_f32(f32_from_rpu_u12_with_bias(u)), u);
+
+            let v = f32_from_rpu_u12_with_bias(rpu_u12_with_bias_from_f32(f));
+            prop_assert!((v - f).abs() <= 1.0 / RPU_U12_BIAS, "{f} -> {v}");
+        }
+
+        #[test]
+        fn test_rpu_u8_round_trip(u in 0..=255_u8, f in -1.0_f32..=1.0) {
+            prop_assert_eq!(rpu_u8_with_bias_from_f32(f32_from_rpu_u8_with_bias(u)), u);
+
+            let v = f32_from_rpu_u8_with_bias(rpu_u8_with_bias_from_f32(f));
+            prop_assert!((v - f).abs() <= 1.0 / RPU_U8_BIAS, "{f} -> {v}");
+        }
+
+        #[test]
+        fn test_sop_round_trip(sop in proptest::array::uniform3(0..=4095_u16)) {
+            let mut trim = TrimSixField([0.0; 6]);
+            for (f, u) in trim.0.iter_mut().zip(sop) {
+                *f = f32_from_rpu_u12_with_bias(u);
+            }
+
+            trim.sop_to_lgg();
+            let lgg = trim;
+            // Values out of range are clamped in LGG
+            prop_assume!(lgg.0[..3].iter().all(|f| f.abs() < 1.0));
+
+            trim.lgg_to_sop();
+            let codes = trim.to_rpu_u12();
+
+            for (u, v) in sop.into_iter().zip(codes) {
+                prop_assert!(u.abs_diff(v) <= 1, "{sop:?} -> {lgg:?} -> {codes:?}");
+            }
+        }
+
+        #[test]
+        fn test_lgg_round_trip(lgg in proptest::array::uniform3(-1.0_f32..=1.0)) {
+            let mut trim = TrimSixField([lgg[0], lgg[1], lgg[2], 0.0, 0.0, 0.0]);
+
+            trim.lgg_to_sop();
+            // Values out of range can't be written in RPU
+            prop_assume!(trim.0[..3].iter().all(|f| (-1.0..=1.0).contains(f)));
+
+            trim.sop_to_lgg();
+
+            for (f, v) in lgg.into_iter().zip(trim.0) {
+                prop_assert!((v - f).abs() <= 1e-4, "{lgg:?} -> {:?}", trim.0);
+            }
+        }
+    }
 }
@@ -1,9 +1,18 @@
 use std::array;
 use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
 
+use anyhow::{bail, Result};
 use chrono::{SecondsFormat, Utc};
 use itertools::Itertools;
-use serde::{Serialize, Serializer};
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 // use serde_aux::prelude::serde_introspect;
 use uuid::Uuid;
 
@@ -19,8 +28,121 @@ pub mod levels;
 pub const XML_PREFIX: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
 pub const DOLBY_XMLNS_PREFIX: &str = "http://www.dolby.com/schemas/dvmd/";
 
+/// DolbyLabsMDF in any supported version.
+#[derive(Debug)]
+pub enum MDFDocument {
+    CMV29(cmv29::DolbyLabsMDF),
+    CMV40(cmv40::DolbyLabsMDF),
+}
+
+// CM v2.9 XML has version as attribute, while CM v4.0 XML has it as element.
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(rename = "@version")]
+    attribute: Option<Version>,
+    #[serde(rename = "Version")]
+    element: Option<Version>,
+}
+
+impl MDFDocument {
+    pub fn from_xml_str(xml: &str) -> Result<Self> {
+        let probe: VersionProbe = quick_xml::de::from_str(xml)?;
+
+        match probe.attribute.or(probe.element) {
+            Some(version) if version < CMV40_MIN_VERSION => {
+                Ok(Self::CMV29(quick_xml::de::from_str(xml)?))
+            }
+            Some(_) => Ok(Self::CMV40(quick_xml::de::from_str(xml)?)),
+            None => bail!("No version found in XML."),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_xml_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn version(&self) -> Version {
+        match self {
+            Self::CMV29(mdf) => mdf.version,
+            Self::CMV40(mdf) => mdf.version,
+        }
+    }
+
+    /// Indented XML with declaration.
+    pub fn to_xml_string(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        self.write_xml(&mut buffer, iter::empty::<Result<cmv40::Shot>>())?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+
+    /// Write indented XML with declaration.
+    /// Shots are appended to the first track one by one as they are taken from the iterator,
+    /// so they don't have to be kept in memory.
+    pub fn write_xml<W, S, I>(&self, writer: W, shots: I) -> Result<()>
+    where
+        W: io::Write,
+        S: Serialize,
+        I: IntoIterator<Item = Result<S>>,
+    {
+        self.write_xml_tracks(writer, iter::once(shots))
+    }
+
+    /// Same as `write_xml`, with shots of each track in document order.
+    pub fn write_xml_tracks<W, S, I, T>(&self, writer: W, tracks: T) -> Result<()>
+    where
+        W: io::Write,
+        S: Serialize,
+        I: IntoIterator<Item = Result<S>>,
+        T: IntoIterator<Item = I>,
+    {
+        let mut serializer_buffer = String::new();
+        let ser = quick_xml::se::Serializer::new(&mut serializer_buffer);
+
+        match self {
+            Self::CMV29(mdf) => mdf.serialize(ser)?,
+            Self::CMV40(mdf) => mdf.serialize(ser)?,
+        };
+
+        let mut writer = Writer::new_with_indent(writer, b' ', 2);
+        writer.get_mut().write_all(XML_PREFIX.as_bytes())?;
+
+        let mut reader = Reader::from_str(&serializer_buffer);
+        reader.config_mut().trim_text(true);
+
+        let mut tracks = tracks.into_iter();
+
+        loop {
+            match reader.read_event()? {
+                Event::Eof => break,
+                Event::End(end) if end.name().as_ref() == b"Track" => {
+                    for shot in tracks.next().into_iter().flatten() {
+                        writer.write_serializable("Shot", &shot?)?;
+                    }
+
+                    writer.write_event(Event::End(end))?;
+                }
+                event => writer.write_event(event)?,
+            }
+        }
+
+        writer.into_inner().flush()?;
+
+        Ok(())
+    }
+}
+
+impl IntoCMV40<cmv40::DolbyLabsMDF> for MDFDocument {
+    fn into_cmv40(self) -> cmv40::DolbyLabsMDF {
+        match self {
+            Self::CMV29(mdf) => mdf.into_cmv40(),
+            Self::CMV40(mdf) => mdf,
+        }
+    }
+}
+
 /// UUID v4.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UUIDv4(String);
 
 impl UUIDv4 {
@@ -29,6 +151,12 @@ impl UUIDv4 {
     }
 }
 
+impl From<Uuid> for UUIDv4 {
+    fn from(uuid: Uuid) -> Self {
+        Self(uuid.to_string())
+    }
+}
+
 impl Default for UUIDv4 {
     fn default() -> Self {
         Self(Uuid::default().to_string())
@@ -96,6 +224,32 @@ where
     }
 }
 
+impl<'de, T, I> Deserialize<'de> for MDFType<T>
+where
+    T: IntoIterator<Item = I> + Copy + TryFrom<Vec<I>>,
+    I: FromStr,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let is_cmv29 = s.contains(',');
+
+        let values = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse::<I>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| D::Error::custom(format!("invalid value in list \"{s}\"")))?;
+
+        let inner = T::try_from(values)
+            .map_err(|_| D::Error::custom(format!("unexpected number of values in \"{s}\"")))?;
+
+        Ok(if is_cmv29 { CMV29(inner) } else { CMV40(inner) })
+    }
+}
+
 impl<T, I> Display for MDFType<T>
 where
     T: IntoIterator<Item = I> + Copy,
@@ -141,16 +295,45 @@ impl<T> IntoCMV29<Self> for MDFType<T> {
         }
     }
 }
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+
+pub trait IntoCMV40<T> {
+    /// Convert inner `MDFType` to `CMV40(T)`.
+    fn into_cmv40(self) -> T;
+}
+
+impl<T, U> IntoCMV40<Option<U>> for Option<T>
+where
+    T: IntoCMV40<U>,
+{
+    fn into_cmv40(self) -> Option<U> {
+        self.map(|i| i.into_cmv40())
+    }
+}
+
+impl<T, U> IntoCMV40<Vec<U>> for Vec<T>
+where
+    T: IntoCMV40<U>,
+{
+    fn into_cmv40(self) -> Vec<U> {
+        self.into_iter().map(|b| b.into_cmv40()).collect::<Vec<_>>()
+    }
+}
+
+impl<T> IntoCMV40<Self> for MDFType<T> {
+    fn into_cmv40(self) -> Self {
+        match self {
+            CMV29(t) | CMV40(t) => CMV40(t),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Encoding {
     #[serde(rename = "$text")]
     pub encoding: EncodingEnum,
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(usize)]
-// FIXME
-#[allow(dead_code)]
 pub enum EncodingEnum {
     #[serde(rename = "pq")]
     #[default]
@@ -169,58 +352,87 @@ pub enum EncodingEnum {
     Hlg,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl EncodingEnum {
+    /// Peak brightness of signal in nits.
+    pub fn peak_brightness(&self) -> usize {
+        match self {
+            Self::Pq => 10000,
+            Self::Hlg => 1000,
+            _ => 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColorSpace {
     #[serde(rename = "$text")]
     pub color_space: ColorSpaceEnum,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ColorSpaceEnum {
     #[serde(rename = "rgb")]
     Rgb,
-    // #[serde(rename = "xyz")]
-    // Xyz,
-    // #[serde(rename = "ycbcr_bt709")]
-    // YCbCrBT709,
-    // #[serde(rename = "ycbcr_bt2020")]
-    // YCbCrBT2020,
-    // #[serde(rename = "ycbcr_native")]
-    // YCbCrNative,
+    #[serde(rename = "xyz")]
+    Xyz,
+    #[serde(rename = "ycbcr_bt709")]
+    YCbCrBT709,
+    #[serde(rename = "ycbcr_bt2020")]
+    YCbCrBT2020,
+    #[serde(rename = "ycbcr_native")]
+    YCbCrNative,
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ApplicationType {
     #[serde(rename = "$text")]
     pub application_type: ApplicationTypeEnum,
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ApplicationTypeEnum {
     #[serde(rename = "ALL")]
     All,
     #[serde(rename = "HOME")]
     Home,
-    // #[serde(rename = "CINEMA")]
-    // Cinema,
+    #[serde(rename = "CINEMA")]
+    Cinema,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignalRange {
     #[serde(rename = "$text")]
     pub signal_range: SignalRangeEnum,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SignalRangeEnum {
     #[serde(rename = "computer")]
     Computer,
-    // #[serde(rename = "video")]
-    // Video,
+    #[serde(rename = "video")]
+    Video,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChromaFormat {
+    #[serde(rename = "$text")]
+    pub chroma_format: ChromaFormatEnum,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChromaFormatEnum {
+    #[serde(rename = "444")]
+    #[default]
+    Yuv444,
+    #[serde(rename = "422")]
+    Yuv422,
+    #[serde(rename = "420")]
+    Yuv420,
 }
 
 pub const XML_VERSION_LIST: &[[usize; 3]] = &[[2, 0, 5], [4, 0, 2], [5, 1, 0]];
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum XMLVersion {
     V205,
     V402,
@@ -287,14 +499,46 @@ impl Serialize for Version {
     }
 }
 
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let values = s
+            .trim()
+            .split('.')
+            .map(|v| v.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match values[..] {
+            [major, minor, revision] => Ok(Self {
+                major,
+                minor,
+                revision,
+            }),
+            _ => bail!("Invalid XML version: {s}"),
+        }
+    }
+}
+
 impl Version {
     // pub fn from_summary(summary:)
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RevisionHistory {
     #[serde(rename = "Revision")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub revisions: Option<Vec<Revision>>,
 }
 
@@ -306,7 +550,13 @@ impl RevisionHistory {
     }
 }
 
-#[derive(Debug, Serialize)]
+impl Default for RevisionHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Revision {
     #[serde(rename = "DateTime")]
     pub date_time: DateTime,
@@ -318,6 +568,7 @@ pub struct Revision {
     pub software_version: String,
     #[serde(rename = "Comment")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub comment: Option<String>,
 }
 
@@ -335,17 +586,43 @@ impl Revision {
     }
 }
 
-#[derive(Debug, Serialize)]
+impl Default for Revision {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DateTime(String);
 
 impl DateTime {
+    /// Time from `SOURCE_DATE_EPOCH` if it's set, for reproducible output.
     pub fn new() -> Self {
-        Self(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true))
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.trim().parse().ok())
+            .and_then(Self::from_timestamp)
+            .unwrap_or_else(|| Self::with_utc(Utc::now()))
+    }
+
+    /// Seconds since Unix epoch.
+    pub fn from_timestamp(secs: i64) -> Option<Self> {
+        chrono::DateTime::from_timestamp(secs, 0).map(Self::with_utc)
+    }
+
+    fn with_utc(date_time: chrono::DateTime<Utc>) -> Self {
+        Self(date_time.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+impl Default for DateTime {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 // Format: f32,f32 in CMv2.9, f32 f32 in CMv4.0
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Primaries {
     #[serde(rename = "Red")]
     pub red: MDFType<Chromaticity>,
@@ -375,6 +652,27 @@ impl IntoCMV29<Self> for Primaries {
     }
 }
 
+impl IntoCMV40<Self> for Primaries {
+    fn into_cmv40(self) -> Self {
+        Self {
+            red: self.red.into_cmv40(),
+            green: self.green.into_cmv40(),
+            blue: self.blue.into_cmv40(),
+        }
+    }
+}
+
+impl Primaries {
+    pub fn with_white_point(&self, white_point: MDFType<Chromaticity>) -> display::Primaries {
+        display::Primaries {
+            red: self.red.into_inner(),
+            green: self.green.into_inner(),
+            blue: self.blue.into_inner(),
+            white_point: white_point.into_inner(),
+        }
+    }
+}
+
 fn update_levels<T: WithTid>(a: &mut Option<Vec<T>>, b: &Option<Vec<T>>) {
     if let Some(b_vec) = b {
         if a.is_none() {
@@ -394,3 +692,110 @@ pub(crate) trait WithTid {
     fn tid(&self) -> usize;
     fn with_tid(tid: usize) -> Self;
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::cmv40::Shot;
+    use crate::MDFType::{CMV29, CMV40};
+    use crate::{
+        cmv29, cmv40, ChromaFormat, ChromaFormatEnum, IntoCMV40, MDFDocument, SignalRangeEnum,
+        XMLVersion,
+    };
+
+    const CMV29_XML: &str = include_str!("../../assets/tests/cmv29.xml");
+    const CMV40_XML: &str = include_str!("../../assets/tests/cmv40.xml");
+
+    fn first_shot(document: MDFDocument) -> Shot {
+        document
+            .into_cmv40()
+            .outputs
+            .outputs
+            .remove(0)
+            .video
+            .tracks
+            .remove(0)
+            .shots
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        for xml in [CMV29_XML, CMV40_XML] {
+            let document = MDFDocument::from_xml_str(xml)?;
+            assert_eq!(document.to_xml_string()?, xml);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_cmv29() -> Result<()> {
+        let document = MDFDocument::from_xml_str(CMV29_XML)?;
+        assert_eq!(document.version(), XMLVersion::V205.into());
+
+        let MDFDocument::CMV29(mdf) = &document else {
+            panic!("Not a CM v2.9 document");
+        };
+        assert!(mdf.source_list.is_some());
+
+        let track = &mdf.outputs.as_ref().unwrap().outputs.as_ref().unwrap()[0]
+            .video
+            .tracks[0];
+        let level2 = &track.shots.as_ref().unwrap()[0].plugin_node.level2;
+        assert!(matches!(level2.as_ref().unwrap()[0].trim, CMV29(_)));
+
+        // Upgraded to CM v4.0 layout
+        let shot = first_shot(document);
+        assert!(matches!(
+            shot.plugin_node.dv_dynamic_data.level2.unwrap()[0].trim,
+            CMV40(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_cmv40() -> Result<()> {
+        let document = MDFDocument::from_xml_str(CMV40_XML)?;
+        assert_eq!(document.version(), XMLVersion::V510.into());
+
+        let shot = first_shot(document);
+        let data = shot.plugin_node.dv_dynamic_data;
+        assert_eq!(data.level1.level, 1);
+        assert!(matches!(data.level2.unwrap()[0].trim, CMV40(_)));
+        assert!(data.level8.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_color_encoding() {
+        let mut color_encoding = cmv40::ColorEncoding::default();
+        let legacy = cmv29::ColorEncoding::from(color_encoding.clone());
+        assert_eq!(legacy.bit_depth, 16);
+        assert_eq!(legacy.chroma_format.chroma_format, ChromaFormatEnum::Yuv444);
+
+        // Defaults are not added to CM v4.0 XML
+        let upgraded = cmv40::ColorEncoding::from(legacy);
+        assert!(upgraded.bit_depth.is_none() && upgraded.chroma_format.is_none());
+
+        color_encoding.bit_depth = Some(10);
+        color_encoding.chroma_format = Some(ChromaFormat {
+            chroma_format: ChromaFormatEnum::Yuv420,
+        });
+        color_encoding.signal_range.signal_range = SignalRangeEnum::Video;
+
+        let legacy = cmv29::ColorEncoding::from(color_encoding);
+        assert_eq!(legacy.signal_range.signal_range, SignalRangeEnum::Video);
+
+        let upgraded = cmv40::ColorEncoding::from(legacy);
+        assert_eq!(upgraded.bit_depth, Some(10));
+        assert_eq!(
+            upgraded.chroma_format.unwrap().chroma_format,
+            ChromaFormatEnum::Yuv420
+        );
+    }
+}
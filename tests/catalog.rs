@@ -0,0 +1,38 @@
+// The catalog is global to the process, so it's tested in its own binary.
+
+use dovi_meta::display::{find_target_id, Catalog, Characteristics, Primaries};
+
+const CATALOG: &str = r#"
+    [[primaries]]
+    index = 19
+    name = "Test OLED"
+    values = [0.68, 0.31, 0.25, 0.70, 0.14, 0.05, 0.3127, 0.329]
+
+    [[target_displays]]
+    id = 200
+    primaries = 19
+    peak_brightness = 1500
+"#;
+
+#[test]
+fn test_install() {
+    let path = std::env::temp_dir().join(format!("dovi_meta_catalog_{}.toml", std::process::id()));
+    std::fs::write(&path, CATALOG).unwrap();
+
+    let catalog = Catalog::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    catalog.install().unwrap();
+    assert!(Catalog::default().install().is_err());
+
+    let primaries = Primaries::from([0.68, 0.31, 0.25, 0.70, 0.14, 0.05, 0.3127, 0.329]);
+    assert_eq!(primaries.get_index(), Some(19));
+    assert_eq!(primaries.get_predefined_index(), None);
+    assert_eq!(Primaries::get_index_primary(19, true), Some(primaries));
+
+    assert_eq!(find_target_id(1500, 19), Some(200));
+    assert_eq!(find_target_id(1000, 2), Some(49));
+
+    let target = Characteristics::get_target_with_id(200).unwrap();
+    assert_eq!(target.name, "1500-nits, Test OLED, ST.2084, Full");
+}